@@ -0,0 +1,189 @@
+//! Price alert evaluation.
+//!
+//! Each alert fires once when its condition is met, then stays quiet until
+//! the price pulls back past the threshold by the configured hysteresis, so
+//! a price hovering at the threshold doesn't fire it over and over. A new
+//! alert only arms once a price doesn't meet its condition, so one that
+//! already holds at startup or after a reload stays quiet.
+
+use crate::config::Alert;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// An alert that fired, ready to be shown as a desktop notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+/// Where an alert is in its fire and re-arm cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arming {
+    /// Not yet seen with its condition unmet.
+    Pending,
+    /// Fires on the next price that meets the condition.
+    Armed,
+    /// Fired; re-arms once the price is back past the hysteresis band.
+    Fired,
+}
+
+/// Alert state for one coin.
+pub struct CoinAlerts {
+    alerts: Vec<Alert>,
+    /// State of each alert, in the same order as `alerts`.
+    arming: Vec<Arming>,
+    /// Low and high of each second's prices for `move` alerts, oldest first.
+    history: VecDeque<(Instant, f64, f64)>,
+    /// How long `history` needs to reach back.
+    window: Duration,
+}
+
+impl CoinAlerts {
+    pub fn new(alerts: &[Alert]) -> Self {
+        let window = alerts.iter()
+            .filter_map(|a| match a {
+                Alert::Move { window, .. } => Some(*window),
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default();
+
+        Self {
+            alerts: alerts.to_vec(),
+            arming: vec![Arming::Pending; alerts.len()],
+            history: VecDeque::new(),
+            window,
+        }
+    }
+
+    /// The alerts this state was built for.
+    pub fn alerts(&self) -> &[Alert] {
+        &self.alerts
+    }
+
+    /// Record a new price and describe every alert it fires. `hysteresis` is
+    /// in percent of the alert's threshold: the price for `above` and
+    /// `below`, the size of the move for `move`.
+    pub fn check(&mut self, price: f64, hysteresis: f64) -> Vec<String> {
+        let now = Instant::now();
+        if !self.window.is_zero() {
+            match self.history.back_mut() {
+                Some(last) if now.duration_since(last.0) < Duration::from_secs(1) => {
+                    last.1 = last.1.min(price);
+                    last.2 = last.2.max(price);
+                }
+                _ => self.history.push_back((now, price, price)),
+            }
+            while self.history.front().is_some_and(|(t, ..)| now.duration_since(*t) > self.window) {
+                self.history.pop_front();
+            }
+        }
+
+        let mut fired = Vec::new();
+        for (alert, arming) in self.alerts.iter().zip(self.arming.iter_mut()) {
+            let (triggered, cleared, message) = match *alert {
+                Alert::Above(threshold) => (
+                    price >= threshold,
+                    price < threshold * (1.0 - hysteresis / 100.0),
+                    format!("Rose above ${}", threshold),
+                ),
+                Alert::Below(threshold) => (
+                    price <= threshold,
+                    price > threshold * (1.0 + hysteresis / 100.0),
+                    format!("Fell below ${}", threshold),
+                ),
+                Alert::Move { percent, window } => {
+                    let recent = self.history.iter()
+                        .filter(|(t, ..)| now.duration_since(*t) <= window);
+                    let low = recent.clone().fold(price, |low, s| low.min(s.1));
+                    let high = recent.fold(price, |high, s| high.max(s.2));
+                    let rise = (price - low) / low * 100.0;
+                    let drop = (high - price) / high * 100.0;
+                    let direction = if rise >= drop { "Up" } else { "Down" };
+                    let moved = rise.max(drop);
+                    (
+                        moved >= percent,
+                        moved < percent * (1.0 - hysteresis / 100.0),
+                        format!("{} {:.1}% in {} min", direction, moved, window.as_secs() / 60),
+                    )
+                }
+            };
+
+            match *arming {
+                Arming::Pending if !triggered => *arming = Arming::Armed,
+                Arming::Armed if triggered => {
+                    *arming = Arming::Fired;
+                    fired.push(message);
+                }
+                Arming::Fired if cleared => *arming = Arming::Armed,
+                _ => {}
+            }
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HYSTERESIS: f64 = 0.5;
+
+    /// Feed `prices` in turn, returning how many alerts each one fired.
+    fn run(alert: Alert, prices: &[f64]) -> Vec<usize> {
+        let mut alerts = CoinAlerts::new(&[alert]);
+        prices.iter().map(|p| alerts.check(*p, HYSTERESIS).len()).collect()
+    }
+
+    #[test]
+    fn above_rearms_below_the_band() {
+        let mut alerts = CoinAlerts::new(&[Alert::Above(100.0)]);
+        assert!(alerts.check(99.0, HYSTERESIS).is_empty());
+        assert_eq!(alerts.check(100.0, HYSTERESIS), vec!["Rose above $100".to_string()]);
+        // Back inside the band, then above again: no refire
+        assert!(alerts.check(99.6, HYSTERESIS).is_empty());
+        assert!(alerts.check(100.2, HYSTERESIS).is_empty());
+        // Past the band re-arms it
+        assert!(alerts.check(99.4, HYSTERESIS).is_empty());
+        assert_eq!(alerts.check(100.0, HYSTERESIS).len(), 1);
+    }
+
+    #[test]
+    fn below_rearms_above_the_band() {
+        assert_eq!(
+            run(Alert::Below(100.0), &[101.0, 100.0, 100.4, 99.0, 100.6, 99.9]),
+            vec![0, 1, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn move_rearms_once_the_move_shrinks() {
+        let alert = Alert::Move { percent: 5.0, window: Duration::from_secs(900) };
+        // The move is measured from the window's low (100) and high (105.5)
+        assert_eq!(
+            run(alert, &[100.0, 105.0, 104.99, 105.5, 104.0, 105.0]),
+            vec![0, 1, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn move_below_hysteresis_still_rearms() {
+        let alert = Alert::Move { percent: 0.5, window: Duration::from_secs(900) };
+        assert_eq!(run(alert, &[100.0, 100.5, 100.2, 100.6]), vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn condition_holding_from_the_start_stays_quiet() {
+        assert_eq!(run(Alert::Above(100.0), &[150.0, 120.0, 99.0, 101.0]), vec![0, 0, 0, 1]);
+        assert_eq!(run(Alert::Below(100.0), &[80.0, 100.0, 100.1, 99.0]), vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn move_reports_direction() {
+        let alert = Alert::Move { percent: 5.0, window: Duration::from_secs(900) };
+        let mut alerts = CoinAlerts::new(&[alert]);
+        alerts.check(100.0, HYSTERESIS);
+        assert_eq!(alerts.check(94.0, HYSTERESIS), vec!["Down 6.0% in 15 min".to_string()]);
+    }
+}
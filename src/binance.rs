@@ -0,0 +1,112 @@
+//! WebSocket connection to Binance combined streams for real-time price updates.
+
+use crate::source::{string_f64, PriceEvent, PriceSink, PriceSource, SessionEnd, SourceError};
+use crate::websocket::{connect_and_stream, Endpoint};
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+
+pub const BINANCE_WS: &str = "wss://stream.binance.com:9443";
+
+/// Envelope used by the combined `/stream` endpoint.
+#[derive(Deserialize)]
+struct StreamMessage {
+    data: Option<MiniTicker>,
+}
+
+/// `<symbol>@miniTicker` payload. Binance sends numbers as strings.
+#[derive(Deserialize)]
+struct MiniTicker {
+    #[serde(rename = "e")]
+    event: Option<String>,
+    #[serde(rename = "s")]
+    symbol: Option<String>,
+    #[serde(rename = "c", default, deserialize_with = "string_f64")]
+    close: Option<f64>,
+    #[serde(rename = "o", default, deserialize_with = "string_f64")]
+    open: Option<f64>,
+    #[serde(rename = "v", default, deserialize_with = "string_f64")]
+    volume: Option<f64>,
+    #[serde(rename = "h", default, deserialize_with = "string_f64")]
+    high: Option<f64>,
+    #[serde(rename = "l", default, deserialize_with = "string_f64")]
+    low: Option<f64>,
+}
+
+/// Binance `miniTicker` combined stream.
+pub struct BinanceSource {
+    endpoint: Endpoint,
+}
+
+impl BinanceSource {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl PriceSource for BinanceSource {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    /// Binance pairs have no separator: `BTC/USDT` becomes `BTCUSDT`.
+    fn native_symbol(&self, symbol: &str) -> String {
+        symbol.replace('/', "").to_uppercase()
+    }
+
+    fn parse(&self, frame: &str) -> Vec<PriceEvent> {
+        parse_mini_ticker(frame)
+    }
+
+    fn stream<'a>(
+        &'a self,
+        symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        // Streams are selected in the URL, so there is nothing to subscribe to
+        let streams: Vec<String> = symbols.iter()
+            .map(|s| format!("{}@miniTicker", s.to_lowercase()))
+            .collect();
+        let endpoint = Endpoint {
+            url: format!(
+                "{}/stream?streams={}",
+                self.endpoint.url.trim_end_matches('/'),
+                streams.join("/")
+            ),
+            ..self.endpoint.clone()
+        };
+
+        Box::pin(async move {
+            connect_and_stream(&endpoint, symbols, Vec::new(), sink, self).await
+        })
+    }
+}
+
+/// Normalize a combined-stream `miniTicker` message into price events.
+fn parse_mini_ticker(text: &str) -> Vec<PriceEvent> {
+    let mut events = Vec::new();
+
+    let Ok(msg) = serde_json::from_str::<StreamMessage>(text) else {
+        return events;
+    };
+    let Some(ticker) = msg.data else {
+        return events;
+    };
+    if ticker.event.as_deref() != Some("24hrMiniTicker") {
+        return events;
+    }
+
+    if let (Some(symbol), Some(price)) = (ticker.symbol, ticker.close) {
+        events.push(PriceEvent::Price { symbol: symbol.clone(), price });
+        if let Some(volume) = ticker.volume {
+            events.push(PriceEvent::Volume { symbol: symbol.clone(), volume });
+        }
+        if let (Some(high), Some(low)) = (ticker.high, ticker.low) {
+            events.push(PriceEvent::Range { symbol: symbol.clone(), high, low });
+        }
+        if let Some(open) = ticker.open.filter(|o| *o > 0.0) {
+            events.push(PriceEvent::Open { symbol, open });
+        }
+    }
+
+    events
+}
@@ -0,0 +1,117 @@
+//! Last known prices, kept across restarts.
+//!
+//! Prices are written to `$XDG_CACHE_HOME/waybar-crypto-ticker/prices.json`
+//! while running and loaded on startup, so the ticker has something to show
+//! before the first live quote arrives. Loaded prices render as stale until
+//! a source updates them.
+
+use crate::ticker::CoinData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Serialize, Deserialize)]
+struct CachedCoin {
+    price: f64,
+    open_24h: f64,
+    #[serde(default)]
+    bid: f64,
+    #[serde(default)]
+    ask: f64,
+    #[serde(default)]
+    volume: f64,
+    #[serde(default)]
+    high: f64,
+    #[serde(default)]
+    low: f64,
+    #[serde(default)]
+    vwap: f64,
+    source: String,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("waybar-crypto-ticker/prices.json"))
+}
+
+/// Read the cached prices, keyed by symbol. A missing or unreadable cache
+/// just means starting without prices.
+pub fn load() -> HashMap<String, CoinData> {
+    let Some(contents) = cache_path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return HashMap::new();
+    };
+    let cached: HashMap<String, CachedCoin> = match serde_json::from_str(&contents) {
+        Ok(cached) => cached,
+        Err(e) => {
+            eprintln!("Warning: Failed to parse price cache: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    cached.into_iter()
+        .filter(|(_, c)| c.price > 0.0)
+        .map(|(symbol, c)| {
+            let data = CoinData {
+                price: c.price,
+                previous_price: 0.0,
+                price_changed: Instant::now(),
+                open_24h: c.open_24h,
+                bid: c.bid,
+                ask: c.ask,
+                volume: c.volume,
+                high: c.high,
+                low: c.low,
+                vwap: c.vwap,
+                updated: Instant::now(),
+                source: c.source,
+                quotes: HashMap::new(),
+                spread: 0.0,
+                spread_alert: false,
+                cached: true,
+            };
+            (symbol, data)
+        })
+        .collect()
+}
+
+/// Write the current prices. The file is replaced atomically so a crash
+/// mid-write can't leave a truncated cache behind.
+pub fn save(prices: &HashMap<String, CoinData>) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    let cached: HashMap<&str, CachedCoin> = prices.iter()
+        .filter(|(_, d)| d.price > 0.0)
+        .map(|(symbol, d)| {
+            let coin = CachedCoin {
+                price: d.price,
+                open_24h: d.open_24h,
+                bid: d.bid,
+                ask: d.ask,
+                volume: d.volume,
+                high: d.high,
+                low: d.low,
+                vwap: d.vwap,
+                source: d.source.clone(),
+            };
+            (symbol.as_str(), coin)
+        })
+        .collect();
+    if cached.is_empty() {
+        return;
+    }
+
+    let result = serde_json::to_string(&cached)
+        .map_err(std::io::Error::from)
+        .and_then(|json| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, json)?;
+            std::fs::rename(&tmp, &path)
+        });
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to write price cache: {}", e);
+    }
+}
@@ -0,0 +1,248 @@
+//! WebSocket connection to Coinbase Advanced Trade for real-time price updates.
+
+use crate::source::{string_f64, PriceEvent, PriceSink, PriceSource, SessionEnd, SourceError};
+use crate::websocket::{connect_and_stream, Endpoint};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+pub const COINBASE_WS: &str = "wss://advanced-trade-ws.coinbase.com";
+
+#[derive(Serialize)]
+struct SubscribeMessage<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    product_ids: &'a [String],
+    channel: &'a str,
+}
+
+#[derive(Deserialize)]
+struct WsMessage {
+    channel: Option<String>,
+    #[serde(default)]
+    events: Vec<TickerEvent>,
+}
+
+#[derive(Deserialize)]
+struct TickerEvent {
+    #[serde(default)]
+    tickers: Vec<TickerData>,
+}
+
+/// Coinbase sends numbers as strings.
+#[derive(Deserialize)]
+struct TickerData {
+    product_id: Option<String>,
+    #[serde(default, deserialize_with = "string_f64")]
+    price: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    best_bid: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    best_ask: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    open_24h: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    price_percent_chg_24_h: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    volume_24_h: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    high_24_h: Option<f64>,
+    #[serde(default, deserialize_with = "string_f64")]
+    low_24_h: Option<f64>,
+}
+
+/// Coinbase Advanced Trade `ticker` channel.
+pub struct CoinbaseSource {
+    endpoint: Endpoint,
+}
+
+impl CoinbaseSource {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl PriceSource for CoinbaseSource {
+    fn name(&self) -> &str {
+        "coinbase"
+    }
+
+    /// Coinbase product IDs use a dash: `BTC/USD` becomes `BTC-USD`.
+    fn native_symbol(&self, symbol: &str) -> String {
+        symbol.replace('/', "-")
+    }
+
+    fn parse(&self, frame: &str) -> Vec<PriceEvent> {
+        parse_ticker(frame)
+    }
+
+    fn stream<'a>(
+        &'a self,
+        symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        Box::pin(async move {
+            // The heartbeats channel keeps quiet products from being disconnected.
+            let mut subscribe = Vec::new();
+            for channel in ["ticker", "heartbeats"] {
+                subscribe.push(serde_json::to_string(&SubscribeMessage {
+                    kind: "subscribe",
+                    product_ids: symbols,
+                    channel,
+                })?);
+            }
+
+            connect_and_stream(&self.endpoint, symbols, subscribe, sink, self).await
+        })
+    }
+
+    /// Only the ticker channel follows the coin list; a heartbeat per
+    /// connection is enough.
+    fn resubscribe(&self, added: &[String], removed: &[String]) -> Option<Vec<String>> {
+        let mut messages = Vec::new();
+        for (kind, product_ids) in [("unsubscribe", removed), ("subscribe", added)] {
+            if !product_ids.is_empty() {
+                let message = SubscribeMessage { kind, product_ids, channel: "ticker" };
+                messages.push(serde_json::to_string(&message).ok()?);
+            }
+        }
+        Some(messages)
+    }
+}
+
+/// Normalize a Coinbase `ticker` channel message into price events.
+fn parse_ticker(text: &str) -> Vec<PriceEvent> {
+    let mut events = Vec::new();
+
+    let Ok(ws_msg) = serde_json::from_str::<WsMessage>(text) else {
+        return events;
+    };
+    if ws_msg.channel.as_deref() != Some("ticker") {
+        return events;
+    }
+
+    for ticker in ws_msg.events.into_iter().flat_map(|e| e.tickers) {
+        if let (Some(symbol), Some(price)) = (ticker.product_id, ticker.price) {
+            events.push(PriceEvent::Price { symbol: symbol.clone(), price });
+            if let (Some(bid), Some(ask)) = (ticker.best_bid, ticker.best_ask) {
+                events.push(PriceEvent::Book { symbol: symbol.clone(), bid, ask });
+            }
+            if let Some(volume) = ticker.volume_24_h {
+                events.push(PriceEvent::Volume { symbol: symbol.clone(), volume });
+            }
+            if let (Some(high), Some(low)) = (ticker.high_24_h, ticker.low_24_h) {
+                events.push(PriceEvent::Range { symbol: symbol.clone(), high, low });
+            }
+
+            // Fall back to the percent change when `open_24h` is absent
+            let open = ticker.open_24h.or_else(|| {
+                ticker.price_percent_chg_24_h.map(|pct| price / (1.0 + pct / 100.0))
+            });
+            if let Some(open) = open.filter(|o| *o > 0.0 && o.is_finite()) {
+                events.push(PriceEvent::Open { symbol, open });
+            }
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::ticker::TickerState;
+    use futures_util::{SinkExt, StreamExt};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
+
+    async fn next_text(ws: &mut WebSocketStream<TcpStream>) -> String {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => text,
+            other => panic!("expected a text frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_from_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let mut received = Vec::new();
+            // Subscribe to both channels
+            received.push(next_text(&mut ws).await);
+            received.push(next_text(&mut ws).await);
+
+            // BTC's open_24h wins over its percent change; ETH has none, so
+            // its open comes from the percent change
+            let ticker = r#"{"channel":"ticker","timestamp":"2024-06-10T14:30:00Z",
+                "sequence_num":1,"events":[{"type":"snapshot","tickers":[
+                {"type":"ticker","product_id":"BTC-USD","price":"66005.5",
+                 "best_bid":"66005.01","best_ask":"66006.2","volume_24_h":"1200.5",
+                 "low_24_h":"64900","high_24_h":"66500","open_24h":"65000",
+                 "price_percent_chg_24_h":"9.99"},
+                {"type":"ticker","product_id":"ETH-USD","price":"3150",
+                 "price_percent_chg_24_h":"5"}]}]}"#;
+            ws.send(Message::Text(ticker.to_string())).await.unwrap();
+
+            // The coin list changes: ETH goes, SOL comes
+            received.push(next_text(&mut ws).await);
+            received.push(next_text(&mut ws).await);
+            ws.close(None).await.unwrap();
+            received
+        });
+
+        let mut config = Config::default();
+        for coin in &mut config.coins {
+            coin.sources = vec!["coinbase".to_string()];
+        }
+        let state = Arc::new(Mutex::new(TickerState::new(&config, false)));
+        let map = |symbols: &[&str]| -> HashMap<String, String> {
+            symbols.iter().map(|s| (s.to_string(), s.replace('-', "/"))).collect()
+        };
+        let sink = PriceSink::new(state.clone(), "coinbase", map(&["BTC-USD", "ETH-USD"]));
+        let symbols = sink.symbols();
+        let endpoint = Endpoint { url, watchdog: None, proxy: None, tls: None };
+        let source = CoinbaseSource::new(endpoint);
+
+        let reload = async {
+            while !state.lock().unwrap().segments.iter().any(|s| s.text.contains("$3150")) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let texts: Vec<String> =
+                state.lock().unwrap().segments.iter().map(|s| s.text.clone()).collect();
+            sink.set_symbols(map(&["BTC-USD", "SOL-USD"]));
+            texts
+        };
+        let (ended, texts) = tokio::join!(source.stream(&symbols, &sink), reload);
+        assert_eq!(ended.unwrap(), SessionEnd::Closed);
+
+        assert!(texts.iter().any(|t| t.contains("$66006 +1.5%")), "{:?}", texts);
+        assert!(texts.iter().any(|t| t.contains("$3150 +5.0%")), "{:?}", texts);
+
+        let received = server.await.unwrap();
+        let message = |i: usize| serde_json::from_str::<serde_json::Value>(&received[i]).unwrap();
+        let (ticker, heartbeats) = (message(0), message(1));
+        assert_eq!(ticker["type"], "subscribe");
+        assert_eq!(ticker["channel"], "ticker");
+        assert_eq!(heartbeats["channel"], "heartbeats");
+        let mut products: Vec<&str> = ticker["product_ids"].as_array().unwrap().iter()
+            .map(|p| p.as_str().unwrap())
+            .collect();
+        products.sort();
+        assert_eq!(products, ["BTC-USD", "ETH-USD"]);
+
+        let (unsubscribe, subscribe) = (message(2), message(3));
+        assert_eq!(unsubscribe["type"], "unsubscribe");
+        assert_eq!(unsubscribe["product_ids"], serde_json::json!(["ETH-USD"]));
+        assert_eq!(subscribe["type"], "subscribe");
+        assert_eq!(subscribe["product_ids"], serde_json::json!(["SOL-USD"]));
+        assert_eq!(unsubscribe["channel"], "ticker");
+        assert_eq!(subscribe["channel"], "ticker");
+    }
+}
@@ -4,7 +4,9 @@
 //! otherwise uses sensible defaults.
 
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Runtime configuration for the ticker.
 #[derive(Debug, Clone)]
@@ -13,6 +15,10 @@ pub struct Config {
     pub position: Position,
     pub appearance: Appearance,
     pub animation: Animation,
+    /// Inline price charts; `None` when disabled.
+    pub sparkline: Option<Sparkline>,
+    pub alerts: AlertSettings,
+    pub source: SourceConfig,
     pub coins: Vec<CoinConfig>,
 }
 
@@ -43,12 +49,132 @@ pub struct Appearance {
     pub color_down: (f64, f64, f64),
     pub color_neutral: (f64, f64, f64),
     pub icon_size: u32,
+    /// Opacity of segments whose quote has gone stale.
+    pub stale_opacity: f64,
+    /// Marker appended to stale segments; empty for none.
+    pub stale_glyph: String,
+    /// Segment text template, e.g. "{price} {change}".
+    pub format: String,
+    /// Template for coins with an `amount`, which can use `{value}`.
+    pub holding_format: String,
+    /// Template for the total portfolio segment.
+    pub portfolio_format: String,
+    /// Template for unrealized P&L after coins with a `cost_basis`; empty for none.
+    pub pnl_format: String,
+    /// Start with holding sizes and values masked.
+    pub privacy: bool,
+    /// Tooltip template shown when hovering a coin; empty for none.
+    pub tooltip_format: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct Animation {
     pub scroll_speed: f64,
     pub fps: u32,
+    /// How long a price change flashes; `None` disables.
+    pub flash: Option<Duration>,
+}
+
+/// Price chart drawn after each coin's text, built from Kraken OHLC candles.
+#[derive(Debug, Clone)]
+pub struct Sparkline {
+    /// How far back the chart reaches.
+    pub window: Duration,
+    /// Candle length in minutes, one of Kraken's OHLC intervals.
+    pub interval: u32,
+    /// Chart width in pixels.
+    pub width: f64,
+}
+
+impl Sparkline {
+    /// Number of candles needed to cover the window.
+    pub fn capacity(&self) -> usize {
+        (self.window.as_secs() / (self.interval as u64 * 60)).max(2) as usize
+    }
+}
+
+/// Settings shared by every coin's price alerts.
+#[derive(Debug, Clone)]
+pub struct AlertSettings {
+    /// How far the price must pull back from a threshold, in percent of it,
+    /// before that alert can fire again.
+    pub hysteresis: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceConfig {
+    /// Source for coins that don't name one (e.g. "kraken").
+    pub name: String,
+    /// Endpoint overrides by source name, e.g. a regional mirror.
+    pub urls: HashMap<String, String>,
+    /// Extra PEM root certificates to trust.
+    pub ca_bundle: Option<PathBuf>,
+    /// Skip certificate checks; only honoured for loopback hosts.
+    pub insecure: bool,
+    /// How long a source may stay silent before a coin fails over to its next one.
+    pub failover_after: Duration,
+    /// Age after which a displayed quote is rendered as stale; `None` disables.
+    pub stale_after: Option<Duration>,
+    /// Reconnect a WebSocket that stays silent this long; `None` disables.
+    pub watchdog: Option<Duration>,
+    /// Proxy URL for all sources; `None` falls back to `HTTPS_PROXY`/`ALL_PROXY`
+    /// and an empty string connects directly.
+    pub proxy: Option<String>,
+    /// User-defined sources from `[[source.custom]]`.
+    pub custom: Vec<CustomSource>,
+}
+
+/// A user-defined price source, referenced from coins by `name`.
+#[derive(Debug, Clone)]
+pub struct CustomSource {
+    pub name: String,
+    pub kind: CustomKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum CustomKind {
+    /// Poll a JSON HTTP API.
+    Rest(RestConfig),
+    /// Stream from a JSON WebSocket API.
+    Generic(GenericConfig),
+    /// Read NDJSON events from stdin or a named pipe.
+    Pipe(PipeConfig),
+}
+
+#[derive(Debug, Clone)]
+pub struct RestConfig {
+    /// Request URL. `{symbols}` expands to every comma-separated symbol;
+    /// `{symbol}` makes one request per symbol.
+    pub url: String,
+    pub interval: Duration,
+    /// JSON pointer to the price, with `{symbol}` expanded.
+    pub price_path: String,
+    /// JSON pointer to the 24h change, with `{symbol}` expanded.
+    pub change_path: Option<String>,
+    /// Whether the 24h change is a percentage rather than a price delta.
+    pub change_percent: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PipeConfig {
+    /// File or FIFO to read; `-` reads stdin.
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenericConfig {
+    pub url: String,
+    /// Message sent after connecting. `{symbols}` expands to a JSON array of
+    /// every symbol; `{symbol}` sends one message per symbol.
+    pub subscribe: Option<String>,
+    /// JSON pointer to the ticker object, or an array of them.
+    pub data_path: String,
+    /// JSON pointers relative to each ticker object.
+    pub symbol_path: String,
+    pub price_path: String,
+    pub change_path: Option<String>,
+    /// Whether the 24h change is a percentage rather than a price delta.
+    pub change_percent: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -56,8 +182,108 @@ pub struct CoinConfig {
     pub symbol: String,
     pub name: String,
     pub icon: String,
+    /// Price sources this coin is streamed from, in order of preference.
+    pub sources: Vec<String>,
+    /// How quotes from several sources are combined.
+    pub aggregate: Aggregate,
+    /// Spread between sources (percent) above which the coin is flagged.
+    pub spread_alert: Option<f64>,
+    /// Per-source symbol overrides, keyed by source name.
+    pub aliases: HashMap<String, String>,
+    /// Units held, for position and portfolio value.
+    pub amount: Option<f64>,
+    /// Average price paid per unit, for unrealized P&L.
+    pub cost_basis: Option<f64>,
+    /// Thresholds that trigger a desktop notification.
+    pub alerts: Vec<Alert>,
 }
 
+/// A price condition that triggers a desktop notification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alert {
+    /// The price rises to or above this.
+    Above(f64),
+    /// The price falls to or below this.
+    Below(f64),
+    /// The price moves this many percent, either way, within the window.
+    Move { percent: f64, window: Duration },
+}
+
+/// How a coin's price is derived when it has several sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    /// Show the first fresh source in order of preference.
+    Failover,
+    /// Median of all fresh sources.
+    Median,
+    /// Average of all fresh sources weighted by 24h volume.
+    Vwap,
+}
+
+impl CoinConfig {
+    /// Symbol to request from `source`, if it differs from `symbol`.
+    pub fn alias(&self, source: &str) -> Option<&str> {
+        self.aliases.get(source).map(String::as_str)
+    }
+}
+
+/// Options given on the command line.
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    /// Write every raw feed frame to this file.
+    pub record: Option<PathBuf>,
+    /// Replay frames from this file instead of connecting.
+    pub replay: Option<PathBuf>,
+    /// Replay speed multiplier; 0 replays as fast as possible.
+    pub replay_speed: f64,
+}
+
+pub const USAGE: &str = "\
+Usage: waybar-crypto-ticker [OPTIONS]
+
+Options:
+  --record <FILE>        Append every raw feed frame to FILE
+  --replay <FILE>        Replay a recording instead of connecting
+  --replay-speed <N>     Replay N times faster than recorded, at least 0.01;
+                         0 = no delay (default 1)
+  -h, --help             Show this help";
+
+/// Slowest replay speed accepted; slower ones stretch gaps past what a
+/// `Duration` can hold.
+const MIN_REPLAY_SPEED: f64 = 0.01;
+
+impl CliOptions {
+    /// Parse arguments, excluding the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self { record: None, replay: None, replay_speed: 1.0 };
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--record" => options.record = Some(PathBuf::from(value()?)),
+                "--replay" => options.replay = Some(PathBuf::from(value()?)),
+                "--replay-speed" => {
+                    let speed = value()?;
+                    options.replay_speed = speed.parse::<f64>()
+                        .ok()
+                        .filter(|s| *s == 0.0 || (s.is_finite() && *s >= MIN_REPLAY_SPEED))
+                        .ok_or(format!("Invalid replay speed: {}", speed))?;
+                }
+                other => return Err(format!("Unknown option: {}", other)),
+            }
+        }
+
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay cannot be combined".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// Candle lengths, in minutes, offered by Kraken's OHLC channel.
+const OHLC_INTERVALS: [u32; 7] = [1, 5, 15, 30, 60, 240, 1440];
+
 /// TOML file structure for deserialization.
 #[derive(Deserialize, Default)]
 #[serde(default)]
@@ -66,6 +292,9 @@ struct ConfigFile {
     position: PositionFile,
     appearance: AppearanceFile,
     animation: AnimationFile,
+    sparkline: SparklineFile,
+    alerts: AlertsFile,
+    source: SourceFile,
     coins: Option<Vec<CoinFile>>,
 }
 
@@ -104,6 +333,14 @@ struct AppearanceFile {
     color_down: String,
     color_neutral: String,
     icon_size: u32,
+    stale_opacity: f64,
+    stale_glyph: String,
+    format: String,
+    holding_format: String,
+    portfolio_format: String,
+    pnl_format: String,
+    privacy: bool,
+    tooltip_format: String,
 }
 
 impl Default for AppearanceFile {
@@ -115,15 +352,59 @@ impl Default for AppearanceFile {
             color_down: "#e05555".to_string(),
             color_neutral: "#888888".to_string(),
             icon_size: 16,
+            stale_opacity: 0.4,
+            stale_glyph: String::new(),
+            format: "{price} {change}".to_string(),
+            holding_format: "{price} {change} ({value})".to_string(),
+            portfolio_format: "Portfolio {value} {change}".to_string(),
+            pnl_format: "{pnl} ({pnl_pct})".to_string(),
+            privacy: false,
+            tooltip_format: concat!(
+                "{name} {price} {change}\n",
+                "H {high}  L {low}  VWAP {vwap}\n",
+                "Volume {volume}",
+            )
+            .to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SparklineFile {
+    hours: u64,
+    interval: u32,
+    width: f64,
+}
+
+impl Default for SparklineFile {
+    fn default() -> Self {
+        Self {
+            hours: 0,
+            interval: 5,
+            width: 40.0,
         }
     }
 }
 
+#[derive(Deserialize)]
+#[serde(default)]
+struct AlertsFile {
+    hysteresis: f64,
+}
+
+impl Default for AlertsFile {
+    fn default() -> Self {
+        Self { hysteresis: 0.5 }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(default)]
 struct AnimationFile {
     scroll_speed: f64,
     fps: u32,
+    flash: f64,
 }
 
 impl Default for AnimationFile {
@@ -131,38 +412,159 @@ impl Default for AnimationFile {
         Self {
             scroll_speed: 30.0,
             fps: 60,
+            flash: 0.8,
         }
     }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize)]
+#[serde(default)]
+struct SourceFile {
+    name: String,
+    failover_after: u64,
+    stale_after: u64,
+    watchdog: u64,
+    proxy: Option<String>,
+    urls: HashMap<String, String>,
+    ca_bundle: Option<PathBuf>,
+    insecure: bool,
+    custom: Vec<CustomSourceFile>,
+}
+
+impl Default for SourceFile {
+    fn default() -> Self {
+        Self {
+            name: "kraken".to_string(),
+            failover_after: 60,
+            stale_after: 300,
+            watchdog: 30,
+            proxy: None,
+            urls: HashMap::new(),
+            ca_bundle: None,
+            insecure: false,
+            custom: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum CustomSourceFile {
+    Rest {
+        name: String,
+        url: String,
+        #[serde(default = "default_poll_interval")]
+        interval: u64,
+        price_path: String,
+        change_path: Option<String>,
+        #[serde(default = "default_change_kind")]
+        change_kind: String,
+    },
+    Generic {
+        name: String,
+        url: String,
+        subscribe: Option<String>,
+        #[serde(default)]
+        data_path: String,
+        symbol_path: String,
+        price_path: String,
+        change_path: Option<String>,
+        #[serde(default = "default_change_kind")]
+        change_kind: String,
+    },
+    Pipe {
+        name: String,
+        path: PathBuf,
+    },
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+fn default_change_kind() -> String {
+    "percent".to_string()
+}
+
+#[derive(Deserialize, Clone, Default)]
 struct CoinFile {
     symbol: String,
     name: String,
     icon: String,
+    source: Option<String>,
+    sources: Option<Vec<String>>,
+    aggregate: Option<String>,
+    spread_alert: Option<f64>,
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    amount: Option<f64>,
+    cost_basis: Option<f64>,
+    #[serde(default)]
+    alerts: Vec<AlertFile>,
+}
+
+/// One `[[coins]]` alert; exactly one of `above`, `below` or `move` is set.
+#[derive(Deserialize, Clone, Default)]
+struct AlertFile {
+    above: Option<f64>,
+    below: Option<f64>,
+    #[serde(rename = "move")]
+    move_by: Option<f64>,
+    /// Window for `move`, in minutes.
+    minutes: Option<u64>,
+}
+
+impl AlertFile {
+    fn parse(&self, symbol: &str) -> Option<Alert> {
+        let alert = match (self.above, self.below, self.move_by) {
+            (Some(price), None, None) if price > 0.0 => Alert::Above(price),
+            (None, Some(price), None) if price > 0.0 => Alert::Below(price),
+            (None, None, Some(percent)) if percent > 0.0 => Alert::Move {
+                percent,
+                window: Duration::from_secs(self.minutes.unwrap_or(60).max(1) * 60),
+            },
+            _ => {
+                eprintln!(
+                    "Warning: {} alert needs one positive above, below or move, ignoring it",
+                    symbol
+                );
+                return None;
+            }
+        };
+        Some(alert)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::from_file(ConfigFile::default())
+    }
 }
 
 impl Config {
     /// Load configuration from file or use defaults.
     pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Load configuration from file, or defaults if there is none. Unlike
+    /// `load`, a file that can't be read or parsed is an error rather than
+    /// a silent fallback to defaults.
+    pub fn try_load() -> Result<Self, String> {
         let config_path = Self::config_path();
 
         let file_config = if config_path.exists() {
-            match std::fs::read_to_string(&config_path) {
-                Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
-                    eprintln!("Warning: Failed to parse config: {}", e);
-                    ConfigFile::default()
-                }),
-                Err(e) => {
-                    eprintln!("Warning: Failed to read config: {}", e);
-                    ConfigFile::default()
-                }
-            }
+            let contents = std::fs::read_to_string(&config_path)
+                .map_err(|e| format!("Failed to read config: {}", e))?;
+            toml::from_str(&contents).map_err(|e| format!("Failed to parse config: {}", e))?
         } else {
             ConfigFile::default()
         };
 
-        Self::from_file(file_config)
+        Ok(Self::from_file(file_config))
     }
 
     fn config_path() -> PathBuf {
@@ -173,6 +575,7 @@ impl Config {
 
     fn from_file(f: ConfigFile) -> Self {
         let coins = f.coins.unwrap_or_else(Self::default_coins);
+        let default_source = f.source.name.to_lowercase();
 
         Self {
             monitor: f.monitor,
@@ -198,26 +601,115 @@ impl Config {
                 color_down: parse_hex_color(&f.appearance.color_down).unwrap_or((0.88, 0.33, 0.33)),
                 color_neutral: parse_hex_color(&f.appearance.color_neutral).unwrap_or((0.53, 0.53, 0.53)),
                 icon_size: f.appearance.icon_size,
+                stale_opacity: f.appearance.stale_opacity.clamp(0.0, 1.0),
+                stale_glyph: f.appearance.stale_glyph,
+                format: f.appearance.format,
+                holding_format: f.appearance.holding_format,
+                portfolio_format: f.appearance.portfolio_format,
+                pnl_format: f.appearance.pnl_format,
+                privacy: f.appearance.privacy,
+                tooltip_format: f.appearance.tooltip_format,
             },
             animation: Animation {
                 scroll_speed: f.animation.scroll_speed,
-                fps: f.animation.fps.max(1).min(120),
+                fps: f.animation.fps.clamp(1, 120),
+                flash: (f.animation.flash > 0.0)
+                    .then(|| Duration::from_secs_f64(f.animation.flash.min(10.0))),
+            },
+            sparkline: (f.sparkline.hours > 0).then(|| Sparkline {
+                window: Duration::from_secs(f.sparkline.hours * 3600),
+                interval: if OHLC_INTERVALS.contains(&f.sparkline.interval) {
+                    f.sparkline.interval
+                } else {
+                    eprintln!(
+                        "Warning: sparkline interval must be one of {:?} minutes, using 5",
+                        OHLC_INTERVALS
+                    );
+                    5
+                },
+                width: f.sparkline.width.max(8.0),
+            }),
+            alerts: AlertSettings {
+                hysteresis: f.alerts.hysteresis.max(0.0),
+            },
+            source: SourceConfig {
+                name: default_source.clone(),
+                failover_after: Duration::from_secs(f.source.failover_after.max(1)),
+                stale_after: (f.source.stale_after > 0)
+                    .then(|| Duration::from_secs(f.source.stale_after)),
+                watchdog: (f.source.watchdog > 0)
+                    .then(|| Duration::from_secs(f.source.watchdog.max(2))),
+                proxy: f.source.proxy,
+                urls: f.source.urls.into_iter().map(|(k, v)| (k.to_lowercase(), v)).collect(),
+                ca_bundle: f.source.ca_bundle,
+                insecure: f.source.insecure,
+                custom: f.source.custom.into_iter().map(|c| match c {
+                    CustomSourceFile::Rest {
+                        name, url, interval, price_path, change_path, change_kind,
+                    } => CustomSource {
+                        name: name.to_lowercase(),
+                        kind: CustomKind::Rest(RestConfig {
+                            url,
+                            interval: Duration::from_secs(interval.max(1)),
+                            price_path,
+                            change_path,
+                            change_percent: change_kind != "absolute",
+                        }),
+                    },
+                    CustomSourceFile::Generic {
+                        name, url, subscribe, data_path, symbol_path, price_path,
+                        change_path, change_kind,
+                    } => CustomSource {
+                        name: name.to_lowercase(),
+                        kind: CustomKind::Generic(GenericConfig {
+                            url,
+                            subscribe,
+                            data_path,
+                            symbol_path,
+                            price_path,
+                            change_path,
+                            change_percent: change_kind != "absolute",
+                        }),
+                    },
+                    CustomSourceFile::Pipe { name, path } => CustomSource {
+                        name: name.to_lowercase(),
+                        kind: CustomKind::Pipe(PipeConfig { path }),
+                    },
+                }).collect(),
             },
             coins: coins.into_iter().map(|c| CoinConfig {
-                symbol: c.symbol,
+                symbol: c.symbol.clone(),
                 name: c.name,
                 icon: c.icon,
+                sources: c.sources
+                    .or_else(|| c.source.map(|s| vec![s]))
+                    .unwrap_or_else(|| vec![default_source.clone()])
+                    .into_iter()
+                    .map(|s| s.to_lowercase())
+                    .collect(),
+                aggregate: match c.aggregate.as_deref() {
+                    Some("median") => Aggregate::Median,
+                    Some("vwap") => Aggregate::Vwap,
+                    _ => Aggregate::Failover,
+                },
+                spread_alert: c.spread_alert,
+                aliases: c.aliases.into_iter()
+                    .map(|(source, symbol)| (source.to_lowercase(), symbol))
+                    .collect(),
+                amount: c.amount.filter(|a| *a > 0.0),
+                cost_basis: c.cost_basis.filter(|c| *c > 0.0),
+                alerts: c.alerts.iter().filter_map(|a| a.parse(&c.symbol)).collect(),
             }).collect(),
         }
     }
 
     fn default_coins() -> Vec<CoinFile> {
         vec![
-            CoinFile { symbol: "BTC/USD".into(), name: "BTC".into(), icon: "btc.svg".into() },
-            CoinFile { symbol: "ETH/USD".into(), name: "ETH".into(), icon: "eth.svg".into() },
-            CoinFile { symbol: "SOL/USD".into(), name: "SOL".into(), icon: "sol.svg".into() },
-            CoinFile { symbol: "ADA/USD".into(), name: "ADA".into(), icon: "ada.svg".into() },
-            CoinFile { symbol: "XRP/USD".into(), name: "XRP".into(), icon: "xrp.svg".into() },
+            CoinFile { symbol: "BTC/USD".into(), name: "BTC".into(), icon: "btc.svg".into(), ..Default::default() },
+            CoinFile { symbol: "ETH/USD".into(), name: "ETH".into(), icon: "eth.svg".into(), ..Default::default() },
+            CoinFile { symbol: "SOL/USD".into(), name: "SOL".into(), icon: "sol.svg".into(), ..Default::default() },
+            CoinFile { symbol: "ADA/USD".into(), name: "ADA".into(), icon: "ada.svg".into(), ..Default::default() },
+            CoinFile { symbol: "XRP/USD".into(), name: "XRP".into(), icon: "xrp.svg".into(), ..Default::default() },
         ]
     }
 
@@ -276,3 +768,53 @@ fn parse_hex_color(hex: &str) -> Option<(f64, f64, f64)> {
 
     Some((r, g, b))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        CliOptions::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn cli_options() {
+        let options = parse(&[]).unwrap();
+        assert_eq!((options.record, options.replay, options.replay_speed), (None, None, 1.0));
+
+        let options = parse(&["--replay", "feed.log", "--replay-speed", "0"]).unwrap();
+        assert_eq!(options.replay, Some(PathBuf::from("feed.log")));
+        assert_eq!(options.replay_speed, 0.0);
+
+        let options = parse(&["--record", "feed.log"]).unwrap();
+        assert_eq!(options.record, Some(PathBuf::from("feed.log")));
+    }
+
+    #[test]
+    fn cli_option_errors() {
+        assert_eq!(parse(&["--record"]).unwrap_err(), "Missing value for --record");
+        assert_eq!(
+            parse(&["--replay", "feed.log", "--replay-speed"]).unwrap_err(),
+            "Missing value for --replay-speed"
+        );
+        assert_eq!(
+            parse(&["--replay-speed", "-2"]).unwrap_err(),
+            "Invalid replay speed: -2"
+        );
+        assert_eq!(
+            parse(&["--replay-speed", "fast"]).unwrap_err(),
+            "Invalid replay speed: fast"
+        );
+        assert_eq!(
+            parse(&["--replay-speed", "1e-300"]).unwrap_err(),
+            "Invalid replay speed: 1e-300"
+        );
+        assert_eq!(parse(&["--replay-speed", "inf"]).unwrap_err(), "Invalid replay speed: inf");
+        assert_eq!(parse(&["--replay-speed", "NaN"]).unwrap_err(), "Invalid replay speed: NaN");
+        assert_eq!(
+            parse(&["--record", "a.log", "--replay", "b.log"]).unwrap_err(),
+            "--record and --replay cannot be combined"
+        );
+        assert_eq!(parse(&["--verbose"]).unwrap_err(), "Unknown option: --verbose");
+    }
+}
@@ -0,0 +1,88 @@
+//! Generic JSON WebSocket source configured entirely from TOML.
+//!
+//! Covers exchanges without a dedicated client: the subscribe message is a
+//! template and prices are located in each frame with JSON pointers.
+
+use crate::config::GenericConfig;
+use crate::source::{
+    json_f64, open_from_change, PriceEvent, PriceSink, PriceSource, SessionEnd, SourceError,
+};
+use crate::websocket::{connect_and_stream, Endpoint};
+use futures_util::future::BoxFuture;
+
+/// WebSocket source driven by a [`GenericConfig`].
+pub struct GenericSource {
+    name: String,
+    endpoint: Endpoint,
+    config: GenericConfig,
+}
+
+impl GenericSource {
+    pub fn new(name: &str, endpoint: Endpoint, config: GenericConfig) -> Self {
+        Self { name: name.to_string(), endpoint, config }
+    }
+
+    /// Expand the subscribe template for `symbols`.
+    fn subscribe_messages(&self, symbols: &[String]) -> Result<Vec<String>, SourceError> {
+        let Some(template) = &self.config.subscribe else {
+            return Ok(Vec::new());
+        };
+
+        if template.contains("{symbol}") {
+            Ok(symbols.iter().map(|s| template.replace("{symbol}", s)).collect())
+        } else {
+            Ok(vec![template.replace("{symbols}", &serde_json::to_string(symbols)?)])
+        }
+    }
+}
+
+impl PriceSource for GenericSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stream<'a>(
+        &'a self,
+        symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        Box::pin(async move {
+            let subscribe = self.subscribe_messages(symbols)?;
+            connect_and_stream(&self.endpoint, symbols, subscribe, sink, self).await
+        })
+    }
+
+    /// Extract price events from a frame using the configured JSON pointers.
+    fn parse(&self, frame: &str) -> Vec<PriceEvent> {
+        let mut events = Vec::new();
+
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(frame) else {
+            return events;
+        };
+        let tickers = match msg.pointer(&self.config.data_path) {
+            Some(serde_json::Value::Array(items)) => items.iter().collect(),
+            Some(item) => vec![item],
+            None => return events,
+        };
+
+        for ticker in tickers {
+            let symbol = match ticker.pointer(&self.config.symbol_path) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                _ => continue,
+            };
+            let Some(price) = ticker.pointer(&self.config.price_path).and_then(json_f64) else {
+                continue;
+            };
+            events.push(PriceEvent::Price { symbol: symbol.clone(), price });
+
+            let change = self.config.change_path.as_ref()
+                .and_then(|path| ticker.pointer(path).and_then(json_f64));
+            let open = change.and_then(|c| open_from_change(price, c, self.config.change_percent));
+            if let Some(open) = open {
+                events.push(PriceEvent::Open { symbol, open });
+            }
+        }
+
+        events
+    }
+}
@@ -1,6 +1,6 @@
 //! waybar-crypto-ticker - A scrolling cryptocurrency ticker overlay for Waybar.
 //!
-//! Displays real-time cryptocurrency prices from Kraken's WebSocket API as a
+//! Displays real-time cryptocurrency prices from exchange WebSocket APIs as a
 //! smooth scrolling overlay that integrates with Waybar on Hyprland/Wayland.
 
 use gtk4::prelude::*;
@@ -12,18 +12,42 @@ use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+mod alert;
+mod binance;
+mod cache;
+mod coinbase;
 mod config;
+mod generic;
 mod hyprland;
+mod notify;
+mod pipe;
+mod proxy;
+mod record;
+mod rest;
+mod source;
 mod ticker;
 mod websocket;
 
-use config::{Anchor, Config};
+use config::{Anchor, CliOptions, Config};
 use ticker::TickerState;
 
 const APP_ID: &str = "io.github.waybar-crypto-ticker";
 const PID_FILE: &str = "/tmp/waybar-crypto-ticker.pid";
 
 fn main() -> glib::ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", config::USAGE);
+        return glib::ExitCode::SUCCESS;
+    }
+    let options = match CliOptions::parse(args.iter().skip(1).cloned()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, config::USAGE);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
     // Ignore real-time signals that Waybar sends to refresh modules
     ignore_realtime_signals();
 
@@ -39,8 +63,10 @@ fn main() -> glib::ExitCode {
         .application_id(APP_ID)
         .build();
 
-    app.connect_activate(build_ui);
-    app.run()
+    app.connect_activate(move |app| build_ui(app, &options));
+
+    // Our options are already handled; don't let GApplication reject them
+    app.run_with_args(&args[..args.len().min(1)])
 }
 
 /// Guard that removes PID file when dropped.
@@ -201,10 +227,10 @@ fn render_png(path: &std::path::Path, size: u32) -> Option<resvg::tiny_skia::Pix
     Some(pixmap)
 }
 
-fn build_ui(app: &Application) {
+fn build_ui(app: &Application, options: &CliOptions) {
     let config = Config::load();
 
-    let state = Arc::new(Mutex::new(TickerState::new(&config)));
+    let state = Arc::new(Mutex::new(TickerState::new(&config, options.replay.is_none())));
     let scroll_offset = Rc::new(RefCell::new(0.0f64));
     let cached_width = Rc::new(RefCell::new(0.0f64));
     let icons = Rc::new(load_icons(&config));
@@ -290,52 +316,76 @@ fn build_ui(app: &Application) {
 
     let last_segments: Rc<RefCell<Vec<ticker::Segment>>> = Rc::new(RefCell::new(Vec::new()));
     let segments_draw = Rc::clone(&last_segments);
+    let last_status = Rc::new(RefCell::new(String::from("Connecting...")));
+    let status_draw = Rc::clone(&last_status);
+
+    // Horizontal extent and tooltip of each segment drawn in the last frame
+    let tooltip_areas: Rc<RefCell<Vec<(f64, f64, String)>>> = Rc::new(RefCell::new(Vec::new()));
+    let areas_draw = Rc::clone(&tooltip_areas);
 
     drawing_area.set_draw_func(move |_, cr, width, height| {
         let segments = match state_draw.try_lock() {
             Ok(state) => {
                 let segs = state.segments.clone();
                 *segments_draw.borrow_mut() = segs.clone();
+                if segs.is_empty() {
+                    *status_draw.borrow_mut() = state.status_text();
+                }
                 segs
             }
             Err(_) => segments_draw.borrow().clone(),
         };
 
         let offset = *scroll_draw.borrow();
+        areas_draw.borrow_mut().clear();
 
         // Clear background
         cr.set_operator(gtk4::cairo::Operator::Clear);
         let _ = cr.paint();
         cr.set_operator(gtk4::cairo::Operator::Over);
 
-        // Font setup
-        cr.select_font_face(
-            &config_draw.appearance.font_family,
-            gtk4::cairo::FontSlant::Normal,
-            gtk4::cairo::FontWeight::Normal,
-        );
+        // Font setup; the portfolio total is set in bold
+        let select_font = |kind: ticker::SegmentKind| {
+            let weight = if kind == ticker::SegmentKind::Portfolio {
+                gtk4::cairo::FontWeight::Bold
+            } else {
+                gtk4::cairo::FontWeight::Normal
+            };
+            cr.select_font_face(
+                &config_draw.appearance.font_family,
+                gtk4::cairo::FontSlant::Normal,
+                weight,
+            );
+        };
+        select_font(ticker::SegmentKind::Coin);
         cr.set_font_size(config_draw.appearance.font_size);
 
         if segments.is_empty() {
             let c = config_draw.appearance.color_neutral;
             cr.set_source_rgb(c.0, c.1, c.2);
             cr.move_to(10.0, (height as f64 + config_draw.appearance.font_size) / 2.0);
-            let _ = cr.show_text("Connecting...");
+            let _ = cr.show_text(&status_draw.borrow());
             return;
         }
 
         // Calculate widths
         let icon_space = config_draw.appearance.icon_size as f64 + 4.0;
+        let chart_width = config_draw.sparkline.as_ref().map_or(0.0, |s| s.width);
+        let chart_space = chart_width + 6.0;
         let mut total_width = 0.0;
         let mut widths: Vec<f64> = Vec::with_capacity(segments.len());
 
         for seg in &segments {
-            let w = match cr.text_extents(&seg.text) {
+            select_font(seg.kind);
+            let mut w = match cr.text_extents(&seg.text) {
                 Ok(ext) => {
                     if seg.icon.is_some() { icon_space + ext.x_advance() } else { ext.x_advance() }
                 }
                 Err(_) => if seg.icon.is_some() { icon_space } else { 0.0 },
             };
+            if seg.chart.is_some() {
+                w += chart_space;
+            }
             widths.push(w);
             total_width += w;
         }
@@ -362,22 +412,74 @@ fn build_ui(app: &Application) {
                 let seg_width = widths[i];
 
                 if x + seg_width > 0.0 && x < width as f64 {
+                    // Dim quotes that stopped updating
+                    let alpha = if seg.stale { config_draw.appearance.stale_opacity } else { 1.0 };
+
                     if let Some(ref icon_name) = seg.icon {
                         if let Some(surface) = icons_draw.get(icon_name) {
                             let _ = cr.set_source_surface(surface, x, icon_y);
-                            let _ = cr.paint();
+                            let _ = cr.paint_with_alpha(alpha);
                         }
                     }
 
                     let text_x = if seg.icon.is_some() { x + icon_space } else { x };
-                    let color = match seg.direction {
+                    let direction_color = |direction| match direction {
                         ticker::Direction::Up => config_draw.appearance.color_up,
                         ticker::Direction::Down => config_draw.appearance.color_down,
                         ticker::Direction::Neutral => config_draw.appearance.color_neutral,
                     };
-                    cr.set_source_rgb(color.0, color.1, color.2);
+                    let mut color = direction_color(seg.direction);
+
+                    // Flash the color of the latest tick, fading back to the 24h color
+                    if let Some((flash, (tick, at))) = config_draw.animation.flash.zip(seg.tick) {
+                        let progress = at.elapsed().as_secs_f64() / flash.as_secs_f64();
+                        if progress < 1.0 {
+                            let from = direction_color(tick);
+                            let mix = |a: f64, b: f64| a + (b - a) * progress;
+                            color = (
+                                mix(from.0, color.0),
+                                mix(from.1, color.1),
+                                mix(from.2, color.2),
+                            );
+                        }
+                    }
+                    cr.set_source_rgba(color.0, color.1, color.2, alpha);
                     cr.move_to(text_x, text_y);
+                    select_font(seg.kind);
                     let _ = cr.show_text(&seg.text);
+
+                    // Sparkline of closes, scaled to the candles' full range
+                    if let Some(ref candles) = seg.chart {
+                        let low = candles.iter().map(|c| c.low).fold(f64::MAX, f64::min);
+                        let high = candles.iter().map(|c| c.high).fold(f64::MIN, f64::max);
+                        let range = (high - low).max(f64::EPSILON);
+                        let chart_x = x + seg_width - chart_space + 4.0;
+                        let chart_height = config_draw.appearance.icon_size as f64;
+                        let step = chart_width / (candles.len() - 1) as f64;
+
+                        for (i, candle) in candles.iter().enumerate() {
+                            let px = chart_x + i as f64 * step;
+                            let py = icon_y + (high - candle.close) / range * chart_height;
+                            if i == 0 { cr.move_to(px, py) } else { cr.line_to(px, py) }
+                        }
+
+                        let first = candles.first().map_or(0.0, |c| c.close);
+                        let last = candles.last().map_or(0.0, |c| c.close);
+                        let color = if last > first {
+                            config_draw.appearance.color_up
+                        } else if last < first {
+                            config_draw.appearance.color_down
+                        } else {
+                            config_draw.appearance.color_neutral
+                        };
+                        cr.set_source_rgba(color.0, color.1, color.2, alpha);
+                        cr.set_line_width(1.0);
+                        let _ = cr.stroke();
+                    }
+
+                    if let Some(ref tooltip) = seg.tooltip {
+                        areas_draw.borrow_mut().push((x, x + seg_width, tooltip.clone()));
+                    }
                 }
                 x += seg_width;
             }
@@ -390,6 +492,19 @@ fn build_ui(app: &Application) {
         }
     });
 
+    drawing_area.set_has_tooltip(true);
+    drawing_area.connect_query_tooltip(move |_, x, _, _, tooltip| {
+        let x = x as f64;
+        let areas = tooltip_areas.borrow();
+        match areas.iter().find(|(start, end, _)| x >= *start && x < *end) {
+            Some((_, _, text)) => {
+                tooltip.set_text(Some(text));
+                true
+            }
+            None => false,
+        }
+    });
+
     window.set_child(Some(&drawing_area));
 
     // Animation timer
@@ -412,11 +527,60 @@ fn build_ui(app: &Application) {
         glib::ControlFlow::Continue
     });
 
-    // WebSocket connection
+    // Re-check staleness and failover even when no updates arrive
+    let state_refresh = Arc::clone(&state);
+    glib::timeout_add_local(Duration::from_secs(1), move || {
+        if let Ok(mut state) = state_refresh.try_lock() {
+            state.refresh();
+        }
+        glib::ControlFlow::Continue
+    });
+
+    // SIGUSR1 toggles privacy mode, e.g. from a keybinding before screen sharing
+    let state_privacy = Arc::clone(&state);
+    glib::unix_signal_add_local(libc::SIGUSR1, move || {
+        if let Ok(mut state) = state_privacy.lock() {
+            let on = state.toggle_privacy();
+            eprintln!("Privacy mode {}", if on { "on" } else { "off" });
+        }
+        glib::ControlFlow::Continue
+    });
+
+    // Quit through the application on SIGTERM and SIGINT (logout, pkill,
+    // Ctrl-C) rather than being killed outright, so shutdown handlers run
+    for signal in [libc::SIGTERM, libc::SIGINT] {
+        let app = app.clone();
+        glib::unix_signal_add_local(signal, move || {
+            app.quit();
+            glib::ControlFlow::Break
+        });
+    }
+
+    // Keep the last known prices for the next start. Replayed prices are
+    // historical, so they aren't cached.
+    if options.replay.is_none() {
+        let state_cache = Arc::clone(&state);
+        glib::timeout_add_local(Duration::from_secs(60), move || {
+            if let Ok(state) = state_cache.try_lock() {
+                state.save_cache();
+            }
+            glib::ControlFlow::Continue
+        });
+
+        let state_exit = Arc::clone(&state);
+        app.connect_shutdown(move |_| {
+            if let Ok(state) = state_exit.lock() {
+                state.save_cache();
+            }
+        });
+    }
+
+    // Price source connection
     let state_ws = Arc::clone(&state);
     let config_ws = (*config).clone();
+    let options_ws = options.clone();
     std::thread::spawn(move || {
-        websocket::run(&state_ws, &config_ws);
+        source::run(&state_ws, &config_ws, &options_ws);
     });
 
     window.present();
@@ -0,0 +1,146 @@
+//! Desktop notifications through the freedesktop Notifications D-Bus
+//! interface, as served by mako, dunst, swaync and friends.
+
+use crate::alert::Notification;
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedReceiver;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+const DESTINATION: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+
+/// Show each notification as it arrives. The session bus is connected on
+/// first use and reconnected after a failure; notifications that can't be
+/// delivered are only logged.
+pub async fn deliver(notifications: UnboundedReceiver<Notification>) {
+    deliver_with(notifications, || Box::pin(Connection::session())).await
+}
+
+/// [`deliver`] over connections opened by `connect` instead of the session
+/// bus.
+async fn deliver_with<F>(mut notifications: UnboundedReceiver<Notification>, mut connect: F)
+where
+    F: FnMut() -> BoxFuture<'static, zbus::Result<Connection>>,
+{
+    let mut connection: Option<Connection> = None;
+
+    while let Some(notification) = notifications.recv().await {
+        if connection.is_none() {
+            match connect().await {
+                Ok(c) => connection = Some(c),
+                Err(e) => {
+                    eprintln!("Warning: Cannot connect to the session bus: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(bus) = &connection else {
+            continue;
+        };
+
+        if let Err(e) = notify(bus, &notification).await {
+            eprintln!("Warning: Failed to send notification: {}", e);
+            connection = None;
+        }
+    }
+}
+
+async fn notify(bus: &Connection, notification: &Notification) -> zbus::Result<()> {
+    let actions: Vec<&str> = Vec::new();
+    let hints: HashMap<&str, Value> = HashMap::new();
+    bus.call_method(
+        Some(DESTINATION),
+        PATH,
+        Some(DESTINATION),
+        "Notify",
+        &(
+            "waybar-crypto-ticker",
+            0u32,
+            "",
+            notification.summary.as_str(),
+            notification.body.as_str(),
+            actions,
+            hints,
+            -1i32,
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixStream;
+    use tokio::sync::mpsc::{self, UnboundedSender};
+    use zbus::zvariant::OwnedValue;
+
+    /// Notification daemon that reports each summary and body it receives.
+    struct Daemon {
+        received: UnboundedSender<(String, String)>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl Daemon {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            _hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            assert_eq!(app_name, "waybar-crypto-ticker");
+            let _ = self.received.send((summary, body));
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_to_the_notification_daemon() {
+        let (server, client) = UnixStream::pair().unwrap();
+        let (received, mut daemon) = mpsc::unbounded_channel();
+        let server = zbus::connection::Builder::unix_stream(server)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(PATH, Daemon { received })
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        let (_server, client) = (server.unwrap(), client.unwrap());
+
+        let (notifier, notifications) = mpsc::unbounded_channel();
+        let notification = |summary: &str| Notification {
+            summary: summary.to_string(),
+            body: "Rose above $100000".to_string(),
+        };
+        notifier.send(notification("Lost")).unwrap();
+        notifier.send(notification("BTC $100,250")).unwrap();
+        drop(notifier);
+
+        // The first attempt finds no bus; that notification is dropped but
+        // later ones still go out
+        let mut attempts = 0;
+        deliver_with(notifications, move || {
+            attempts += 1;
+            let connection = (attempts > 1).then(|| client.clone());
+            Box::pin(async move { connection.ok_or(zbus::Error::Failure("no bus".into())) })
+        })
+        .await;
+
+        // Each call waits for the daemon's reply, so it has seen them all
+        assert_eq!(
+            daemon.try_recv().ok(),
+            Some(("BTC $100,250".to_string(), "Rose above $100000".to_string()))
+        );
+        assert!(daemon.try_recv().is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Newline-delimited JSON price input from stdin or a named pipe.
+//!
+//! Each line is one event: `{"symbol": "FUND/USD", "price": 12.3, "open": 12.0}`.
+//! `price` and `open` are both optional, so either can be pushed on its own.
+
+use crate::config::PipeConfig;
+use crate::source::{PriceEvent, PriceSink, PriceSource, SessionEnd, SourceError};
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use std::os::unix::fs::FileTypeExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+#[derive(Deserialize)]
+struct PipeEvent {
+    symbol: String,
+    price: Option<f64>,
+    open: Option<f64>,
+}
+
+/// Reads NDJSON events from `path`, or stdin when the path is `-`.
+pub struct PipeSource {
+    name: String,
+    config: PipeConfig,
+}
+
+impl PipeSource {
+    pub fn new(name: &str, config: PipeConfig) -> Self {
+        Self { name: name.to_string(), config }
+    }
+}
+
+impl PriceSource for PipeSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stream<'a>(
+        &'a self,
+        _symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        Box::pin(async move {
+            let stdin = self.config.path.as_os_str() == "-";
+
+            loop {
+                // Opening a FIFO blocks until a writer connects
+                let input: Box<dyn AsyncRead + Unpin + Send> = if stdin {
+                    Box::new(tokio::io::stdin())
+                } else {
+                    Box::new(tokio::fs::File::open(&self.config.path).await?)
+                };
+
+                sink.connected();
+
+                let mut lines = BufReader::new(input).lines();
+                while let Some(line) = lines.next_line().await? {
+                    sink.emit(parse_line(&line));
+                }
+
+                // A FIFO is reopened right away for the next writer, without
+                // the reconnect backoff. Stdin or a regular file won't get
+                // any more input, so stop rather than retry forever.
+                let fifo = !stdin && tokio::fs::metadata(&self.config.path).await?
+                    .file_type()
+                    .is_fifo();
+                if !fifo {
+                    eprintln!("{}: end of input, no more prices from it", self.name);
+                    std::future::pending::<()>().await;
+                }
+            }
+        })
+    }
+}
+
+/// Parse one NDJSON line, ignoring blank or malformed input.
+fn parse_line(line: &str) -> Vec<PriceEvent> {
+    let mut events = Vec::new();
+
+    let line = line.trim();
+    if line.is_empty() {
+        return events;
+    }
+    let event = match serde_json::from_str::<PipeEvent>(line) {
+        Ok(event) => event,
+        Err(e) => {
+            eprintln!("Pipe: ignoring malformed line: {}", e);
+            return events;
+        }
+    };
+
+    if let Some(price) = event.price.filter(|p| *p > 0.0) {
+        events.push(PriceEvent::Price { symbol: event.symbol.clone(), price });
+    }
+    if let Some(open) = event.open.filter(|o| *o > 0.0) {
+        events.push(PriceEvent::Open { symbol: event.symbol, open });
+    }
+
+    events
+}
@@ -0,0 +1,293 @@
+//! HTTP polling source for JSON price APIs such as CoinGecko.
+
+use crate::config::{RestConfig, SourceConfig};
+use crate::source::{
+    json_f64, open_from_change, tls_connector, PriceEvent, PriceSink, PriceSource, SessionEnd,
+    SourceError,
+};
+use futures_util::future::BoxFuture;
+use url::form_urlencoded;
+
+/// Polls a JSON endpoint on a fixed interval.
+pub struct RestSource {
+    name: String,
+    config: RestConfig,
+    client: reqwest::Client,
+}
+
+impl RestSource {
+    /// Proxy and TLS settings come from `[source]`. Without a `proxy` key
+    /// reqwest honours the proxy environment variables by itself.
+    pub fn new(name: &str, config: RestConfig, source: &SourceConfig) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(concat!("waybar-crypto-ticker/", env!("CARGO_PKG_VERSION")))
+            .timeout(std::time::Duration::from_secs(15));
+        if let Some(tls) = tls_connector(source, &config.url) {
+            builder = builder.use_preconfigured_tls(tls);
+        }
+        match source.proxy.as_deref() {
+            Some("") => builder = builder.no_proxy(),
+            Some(url) => match reqwest::Proxy::all(url) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(e) => eprintln!("Warning: invalid proxy URL {}: {}", url, e),
+            },
+            None => {}
+        }
+        let client = builder.build().unwrap_or_default();
+
+        Self { name: name.to_string(), config, client }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<serde_json::Value, SourceError> {
+        let body = self.client.get(url).send().await?.error_for_status()?.text().await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Extract price events for `symbols` from a response body.
+    fn parse(&self, body: &serde_json::Value, symbols: &[String]) -> Vec<PriceEvent> {
+        let mut events = Vec::new();
+
+        for symbol in symbols {
+            let token = pointer_token(symbol);
+            let pointer = self.config.price_path.replace("{symbol}", &token);
+            let Some(price) = body.pointer(&pointer).and_then(json_f64) else {
+                continue;
+            };
+            events.push(PriceEvent::Price { symbol: symbol.clone(), price });
+
+            let change = self.config.change_path.as_ref()
+                .map(|path| path.replace("{symbol}", &token))
+                .and_then(|pointer| body.pointer(&pointer).and_then(json_f64));
+            let open = change.and_then(|c| open_from_change(price, c, self.config.change_percent));
+            if let Some(open) = open {
+                events.push(PriceEvent::Open { symbol: symbol.clone(), open });
+            }
+        }
+
+        events
+    }
+}
+
+impl PriceSource for RestSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stream<'a>(
+        &'a self,
+        _symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        Box::pin(async move {
+            let mut interval = tokio::time::interval(self.config.interval);
+
+            loop {
+                interval.tick().await;
+                // Each poll asks for the current coin list, so reloads need no reconnect
+                let symbols = &sink.symbols();
+
+                if self.config.url.contains("{symbol}") {
+                    // One bad symbol mustn't hold up the rest; the session only
+                    // fails when nothing could be fetched
+                    let mut failure = None;
+                    let mut fetched = false;
+                    for symbol in symbols {
+                        let url = self.config.url.replace("{symbol}", &encode(symbol));
+                        match self.fetch(&url).await {
+                            Ok(body) => {
+                                fetched = true;
+                                sink.connected();
+                                sink.emit(self.parse(&body, std::slice::from_ref(symbol)));
+                            }
+                            Err(e) => {
+                                eprintln!("Warning: {}: {} failed: {}", self.name, symbol, e);
+                                failure = Some(e);
+                            }
+                        }
+                    }
+                    if let (false, Some(e)) = (fetched, failure) {
+                        return Err(e);
+                    }
+                } else {
+                    let encoded: Vec<String> = symbols.iter().map(|s| encode(s)).collect();
+                    let url = self.config.url.replace("{symbols}", &encoded.join(","));
+                    let body = self.fetch(&url).await?;
+                    sink.connected();
+                    sink.emit(self.parse(&body, symbols));
+                }
+            }
+        })
+    }
+}
+
+/// Escape a symbol for use as one JSON pointer reference token, so that
+/// e.g. `BTC/USD` looks up a single key rather than two nested ones.
+fn pointer_token(symbol: &str) -> String {
+    symbol.replace('~', "~0").replace('/', "~1")
+}
+
+/// Percent-encode a symbol for use in a URL.
+fn encode(symbol: &str) -> String {
+    form_urlencoded::byte_serialize(symbol.as_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::ticker::TickerState;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    /// Serve HTTP on a local port, answering each request with the status
+    /// and body `respond` gives for its path. Returns the base URL and the
+    /// paths requested.
+    async fn serve(respond: fn(&str) -> (u16, String)) -> (String, UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (paths, requested) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    head.extend_from_slice(&buf[..n]);
+                }
+                let head = String::from_utf8_lossy(&head).into_owned();
+                let path = head.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let (status, body) = respond(&path);
+                let response = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = paths.send(path);
+            }
+        });
+        (url, requested)
+    }
+
+    /// A source polling `url` every hour, so each test sees a single poll.
+    fn source(url: String) -> RestSource {
+        let config = RestConfig {
+            url,
+            interval: Duration::from_secs(3600),
+            price_path: "/{symbol}/usd".to_string(),
+            change_path: Some("/{symbol}/usd_24h_change".to_string()),
+            change_percent: true,
+        };
+        let mut settings = Config::default().source;
+        settings.proxy = Some(String::new());
+        RestSource::new("stub", config, &settings)
+    }
+
+    /// Ticker state for the default coins streamed from the stub, and a
+    /// sink mapping API IDs to them. `nope` stands for an unknown coin.
+    fn sink() -> (Arc<Mutex<TickerState>>, PriceSink) {
+        let mut config = Config::default();
+        for coin in &mut config.coins {
+            coin.sources = vec!["stub".to_string()];
+        }
+        let state = Arc::new(Mutex::new(TickerState::new(&config, false)));
+        let symbols = [("bitcoin", "BTC/USD"), ("ethereum", "ETH/USD"), ("nope", "SOL/USD")]
+            .iter()
+            .map(|(id, symbol)| (id.to_string(), symbol.to_string()))
+            .collect::<HashMap<_, _>>();
+        (state.clone(), PriceSink::new(state, "stub", symbols))
+    }
+
+    /// Wait until the ticker shows both stub prices.
+    async fn priced(state: &Mutex<TickerState>) {
+        let shown = || {
+            let state = state.lock().unwrap();
+            let texts: Vec<&str> = state.segments.iter().map(|s| s.text.as_str()).collect();
+            texts.iter().any(|t| t.contains("$66000 +5.0%"))
+                && texts.iter().any(|t| t.contains("$3150"))
+        };
+        while !shown() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    const PRICES: &str =
+        r#"{"bitcoin":{"usd":66000,"usd_24h_change":5.0},"ethereum":{"usd":"3150"}}"#;
+
+    #[test]
+    fn symbols_with_slashes_are_escaped() {
+        let body = serde_json::json!({ "BTC/USD": { "usd": 65000.0 }, "a~b": 1.0 });
+        let pointer = "/{symbol}/usd".replace("{symbol}", &pointer_token("BTC/USD"));
+        assert_eq!(pointer, "/BTC~1USD/usd");
+        assert_eq!(body.pointer(&pointer).and_then(json_f64), Some(65000.0));
+        let pointer = format!("/{}", pointer_token("a~b"));
+        assert_eq!(pointer, "/a~0b");
+        assert_eq!(body.pointer(&pointer).and_then(json_f64), Some(1.0));
+
+        assert_eq!(encode("BTC/USD"), "BTC%2FUSD");
+        assert_eq!(encode("bitcoin"), "bitcoin");
+    }
+
+    #[tokio::test]
+    async fn polls_all_symbols_at_once() {
+        let (url, mut requested) = serve(|_| (200, PRICES.to_string())).await;
+        let source = source(format!("{}/price?ids={{symbols}}&vs=usd", url));
+        let (state, sink) = sink();
+
+        tokio::select! {
+            ended = source.stream(&[], &sink) => panic!("session ended: {:?}", ended.err()),
+            _ = priced(&state) => {}
+        }
+
+        let path = requested.recv().await.unwrap();
+        let ids = path.strip_prefix("/price?ids=").unwrap().strip_suffix("&vs=usd").unwrap();
+        let mut ids: Vec<&str> = ids.split(',').collect();
+        ids.sort();
+        assert_eq!(ids, ["bitcoin", "ethereum", "nope"]);
+        assert!(requested.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn one_failing_symbol_does_not_stop_the_rest() {
+        let (url, mut requested) = serve(|path| match path {
+            "/coins/nope" => (404, r#"{"error":"coin not found"}"#.to_string()),
+            _ => (200, PRICES.to_string()),
+        })
+        .await;
+        let source = source(format!("{}/coins/{{symbol}}", url));
+        let (state, sink) = sink();
+
+        tokio::select! {
+            ended = source.stream(&[], &sink) => panic!("session ended: {:?}", ended.err()),
+            _ = priced(&state) => {}
+        }
+
+        let mut paths = Vec::new();
+        while paths.len() < 3 {
+            paths.push(requested.recv().await.unwrap());
+        }
+        paths.sort();
+        assert_eq!(paths, ["/coins/bitcoin", "/coins/ethereum", "/coins/nope"]);
+    }
+
+    #[tokio::test]
+    async fn session_fails_when_every_symbol_fails() {
+        let (url, _requested) = serve(|_| (500, String::new())).await;
+        let source = source(format!("{}/coins/{{symbol}}", url));
+        let (_state, sink) = sink();
+
+        let ended = tokio::time::timeout(Duration::from_secs(10), source.stream(&[], &sink)).await;
+        let error = ended.expect("session kept going").unwrap_err();
+        assert!(error.to_string().contains("500"), "{}", error);
+    }
+}
@@ -0,0 +1,556 @@
+//! Price source abstraction.
+//!
+//! A price source connects to an exchange (or any other feed), normalizes
+//! whatever it receives into [`PriceEvent`]s and hands them to a
+//! [`PriceSink`], which applies them to the shared [`TickerState`].
+
+use crate::binance::{BinanceSource, BINANCE_WS};
+use crate::coinbase::{CoinbaseSource, COINBASE_WS};
+use crate::config::{CliOptions, CoinConfig, Config, CustomKind, SourceConfig};
+use crate::generic::GenericSource;
+use crate::notify;
+use crate::pipe::PipeSource;
+use crate::proxy::Proxy;
+use crate::record::{self, Recorder};
+use crate::rest::RestSource;
+use crate::ticker::{Candle, ConnectionState, TickerState};
+use crate::websocket::{Endpoint, KrakenSource, KRAKEN_WS};
+use futures_util::future::{join_all, BoxFuture};
+use serde::{Deserialize, Deserializer};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+
+/// First reconnect delay after a failure.
+const BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+/// Upper bound for the reconnect delay.
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// A session that stays up this long resets the backoff.
+const HEALTHY_SESSION: Duration = Duration::from_secs(30);
+
+/// Error type returned by a streaming session.
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Why a streaming session ended without an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+    /// The server closed the connection.
+    Closed,
+    /// The symbols changed and the source can't resubscribe in place, so
+    /// it should reconnect right away.
+    SymbolsChanged,
+}
+
+/// Normalized update emitted by a price source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceEvent {
+    /// Latest traded price.
+    Price { symbol: String, price: f64 },
+    /// Price 24 hours ago, used for the change percentage.
+    Open { symbol: String, open: f64 },
+    /// Best bid and ask on the order book.
+    Book { symbol: String, bid: f64, ask: f64 },
+    /// Traded volume over the last 24 hours, in the base asset.
+    Volume { symbol: String, volume: f64 },
+    /// Highest and lowest traded price over the last 24 hours.
+    Range { symbol: String, high: f64, low: f64 },
+    /// Volume-weighted average price over the last 24 hours.
+    Vwap { symbol: String, vwap: f64 },
+    /// An OHLC candle, used for sparklines.
+    Candle { symbol: String, candle: Candle },
+    /// The source refused to stream this symbol, e.g. an unknown pair.
+    Rejected { symbol: String, reason: String },
+}
+
+impl PriceEvent {
+    /// Native symbol the event refers to.
+    pub fn symbol(&self) -> &str {
+        match self {
+            PriceEvent::Price { symbol, .. }
+            | PriceEvent::Open { symbol, .. }
+            | PriceEvent::Book { symbol, .. }
+            | PriceEvent::Volume { symbol, .. }
+            | PriceEvent::Range { symbol, .. }
+            | PriceEvent::Vwap { symbol, .. }
+            | PriceEvent::Candle { symbol, .. }
+            | PriceEvent::Rejected { symbol, .. } => symbol,
+        }
+    }
+}
+
+/// A feed of price updates for a set of symbols.
+pub trait PriceSource: Send + Sync {
+    /// Identifier used in the config file and in log messages.
+    fn name(&self) -> &str;
+
+    /// Convert a `BTC/USD` style symbol into the exchange's own pair format.
+    fn native_symbol(&self, symbol: &str) -> String {
+        symbol.to_string()
+    }
+
+    /// Connect, subscribe to `symbols` (in native format) and forward
+    /// updates to `sink` until the connection ends, returning why it ended.
+    /// Reconnection is handled by the caller.
+    fn stream<'a>(
+        &'a self,
+        symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>>;
+
+    /// Normalize one raw frame, as passed to [`PriceSink::record`], into
+    /// price events. Used to replay recordings; sources that never record
+    /// frames can keep the default.
+    fn parse(&self, _frame: &str) -> Vec<PriceEvent> {
+        Vec::new()
+    }
+
+    /// Messages that change a live subscription, subscribing to `added` and
+    /// unsubscribing from `removed` (both in native format). `None` means the
+    /// source can't change symbols in place and reconnects instead.
+    fn resubscribe(&self, _added: &[String], _removed: &[String]) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// Applies normalized events to the shared ticker state, translating
+/// native symbols back into the config symbols `TickerState` is keyed by.
+///
+/// The symbol map is held in a watch channel so a running session can
+/// follow changes to the coin list without reconnecting.
+pub struct PriceSink {
+    state: Arc<Mutex<TickerState>>,
+    source: String,
+    symbols: watch::Sender<HashMap<String, String>>,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl PriceSink {
+    /// `symbols` maps each native symbol to its config symbol.
+    pub fn new(
+        state: Arc<Mutex<TickerState>>,
+        source: &str,
+        symbols: HashMap<String, String>,
+    ) -> Self {
+        let (symbols, _) = watch::channel(symbols);
+        Self { state, source: source.to_string(), symbols, recorder: None }
+    }
+
+    /// Native symbols the source should currently stream.
+    pub fn symbols(&self) -> Vec<String> {
+        self.symbols.borrow().keys().cloned().collect()
+    }
+
+    /// Replace the symbol map, notifying running sessions if it changed.
+    pub fn set_symbols(&self, symbols: HashMap<String, String>) {
+        self.symbols.send_if_modified(|current| {
+            let changed = *current != symbols;
+            *current = symbols;
+            changed
+        });
+    }
+
+    /// Receiver that is notified whenever [`PriceSink::set_symbols`] changes
+    /// the map.
+    pub fn watch_symbols(&self) -> watch::Receiver<HashMap<String, String>> {
+        self.symbols.subscribe()
+    }
+
+    /// Write every raw frame passed to [`PriceSink::record`] to `recorder`.
+    pub fn with_recorder(mut self, recorder: Option<Arc<Recorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Report that the session is established and receiving data.
+    pub fn connected(&self) {
+        self.set_connection(ConnectionState::Live);
+    }
+
+    fn set_connection(&self, connection: ConnectionState) {
+        if let Ok(mut state) = self.state.lock() {
+            state.set_connection(&self.source, connection);
+        }
+    }
+
+    /// Record a raw frame as received, before parsing.
+    pub fn record(&self, frame: &str) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.source, frame);
+        }
+    }
+
+    /// Apply a batch of events under a single lock.
+    pub fn emit(&self, events: Vec<PriceEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let symbols = self.symbols.borrow();
+
+        if let Ok(mut state) = self.state.lock() {
+            for event in events {
+                // Frames can still arrive for symbols that were just removed
+                let Some(symbol) = symbols.get(event.symbol()) else {
+                    continue;
+                };
+                match event {
+                    PriceEvent::Price { price, .. } => {
+                        state.update_price(&self.source, symbol, price)
+                    }
+                    PriceEvent::Open { open, .. } => {
+                        state.set_open_price(&self.source, symbol, open)
+                    }
+                    PriceEvent::Book { bid, ask, .. } => {
+                        state.set_book(&self.source, symbol, bid, ask)
+                    }
+                    PriceEvent::Volume { volume, .. } => {
+                        state.set_volume(&self.source, symbol, volume)
+                    }
+                    PriceEvent::Range { high, low, .. } => {
+                        state.set_range(&self.source, symbol, high, low)
+                    }
+                    PriceEvent::Vwap { vwap, .. } => state.set_vwap(&self.source, symbol, vwap),
+                    PriceEvent::Candle { candle, .. } => state.add_candle(symbol, candle),
+                    PriceEvent::Rejected { reason, .. } => {
+                        state.set_error(&self.source, symbol, &reason)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Build a source by the name used in the config file, checking
+/// `[[source.custom]]` entries before the built-in exchanges.
+fn build(name: &str, config: &Config) -> Option<Box<dyn PriceSource>> {
+    let proxy = Proxy::resolve(config.source.proxy.as_deref());
+    let endpoint = |url: &str| Endpoint {
+        url: url.to_string(),
+        watchdog: config.source.watchdog,
+        proxy: proxy.clone(),
+        tls: tls_connector(&config.source, url),
+    };
+
+    if let Some(custom) = config.source.custom.iter().find(|c| c.name == name) {
+        return match &custom.kind {
+            CustomKind::Rest(rest) => {
+                Some(Box::new(RestSource::new(name, rest.clone(), &config.source)))
+            }
+            CustomKind::Generic(generic) => Some(Box::new(GenericSource::new(
+                name,
+                endpoint(&generic.url),
+                generic.clone(),
+            ))),
+            CustomKind::Pipe(pipe) => Some(Box::new(PipeSource::new(name, pipe.clone()))),
+        };
+    }
+
+    let url = config.source.urls.get(name).map(String::as_str);
+    match name {
+        "kraken" => {
+            let source = KrakenSource::new(endpoint(url.unwrap_or(KRAKEN_WS)));
+            Some(Box::new(match &config.sparkline {
+                Some(sparkline) => source.with_candles(sparkline.interval),
+                None => source,
+            }))
+        }
+        "coinbase" => Some(Box::new(CoinbaseSource::new(endpoint(url.unwrap_or(COINBASE_WS))))),
+        "binance" => Some(Box::new(BinanceSource::new(endpoint(url.unwrap_or(BINANCE_WS))))),
+        _ => None,
+    }
+}
+
+/// TLS settings for connecting to `url`: extra roots from `ca_bundle`, and
+/// no certificate checks when `insecure` is set and the host is loopback.
+/// `None` leaves the platform defaults in place.
+pub fn tls_connector(source: &SourceConfig, url: &str) -> Option<native_tls::TlsConnector> {
+    let host = url::Url::parse(url).ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let insecure = source.insecure && is_loopback(&host);
+    if source.insecure && !insecure {
+        eprintln!("Warning: insecure is only honoured for localhost, verifying {}", host);
+    }
+    if source.ca_bundle.is_none() && !insecure {
+        return None;
+    }
+
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(path) = &source.ca_bundle {
+        let certs = std::fs::read(path).map_err(SourceError::from).and_then(|pem| {
+            native_tls::Certificate::stack_from_pem(&pem).map_err(SourceError::from)
+        });
+        match certs {
+            Ok(certs) => {
+                for cert in certs {
+                    builder.add_root_certificate(cert);
+                }
+            }
+            Err(e) => eprintln!("Warning: could not load CA bundle {}: {}", path.display(), e),
+        }
+    }
+    if insecure {
+        builder.danger_accept_invalid_certs(true).danger_accept_invalid_hostnames(true);
+    }
+
+    builder.build()
+        .map_err(|e| eprintln!("Warning: TLS setup for {} failed: {}", host, e))
+        .ok()
+}
+
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Run one connection per distinct source used by the configured coins,
+/// all feeding the same ticker state. With `--replay`, the recording is fed
+/// through the same sources' parsers instead.
+///
+/// On SIGHUP the coin list is reloaded from the config file and each running
+/// source is resubscribed to its new symbols.
+#[tokio::main]
+pub async fn run(state: &Arc<Mutex<TickerState>>, config: &Config, options: &CliOptions) {
+    let sources = connections(state, config);
+
+    if let Some(path) = &options.replay {
+        let sources = sources.into_iter()
+            .map(|(source, sink)| (source.name().to_string(), (source, sink)))
+            .collect();
+        if let Err(e) = record::replay(path, options.replay_speed, &sources).await {
+            eprintln!("Replay of {} failed: {}", path.display(), e);
+        }
+        return;
+    }
+
+    let (notifier, notifications) = mpsc::unbounded_channel();
+    if let Ok(mut state) = state.lock() {
+        state.set_notifier(notifier);
+    }
+
+    let recorder = options.record.as_ref().and_then(|path| match Recorder::create(path) {
+        Ok(recorder) => Some(Arc::new(recorder)),
+        Err(e) => {
+            eprintln!("Warning: Cannot record to {}: {}", path.display(), e);
+            None
+        }
+    });
+
+    let sources: Vec<_> = sources.into_iter()
+        .map(|(source, sink)| (source, Arc::new(sink.with_recorder(recorder.clone()))))
+        .collect();
+    let mut tasks: Vec<_> = sources.iter()
+        .map(|(source, sink)| tokio::spawn(stream_forever(Arc::clone(source), Arc::clone(sink))))
+        .collect();
+    tasks.push(tokio::spawn(notify::deliver(notifications)));
+    tasks.push(tokio::spawn(reload_on_hangup(Arc::clone(state), sources)));
+
+    join_all(tasks).await;
+}
+
+/// Group the configured coins by each source they list.
+fn coins_by_source(config: &Config) -> BTreeMap<&str, Vec<&CoinConfig>> {
+    let mut groups: BTreeMap<&str, Vec<&CoinConfig>> = BTreeMap::new();
+    for coin in &config.coins {
+        for name in &coin.sources {
+            groups.entry(name.as_str()).or_default().push(coin);
+        }
+    }
+    groups
+}
+
+/// Map `source`'s native symbol for each coin to the coin's config symbol.
+/// Per-coin aliases take precedence over the source's own conversion.
+fn symbol_map(source: &dyn PriceSource, coins: &[&CoinConfig]) -> HashMap<String, String> {
+    coins.iter()
+        .map(|coin| {
+            let symbol = coin.alias(source.name()).unwrap_or(&coin.symbol);
+            (source.native_symbol(symbol), coin.symbol.clone())
+        })
+        .collect()
+}
+
+/// Build each source used by the configured coins, paired with a sink that
+/// maps its native symbols back to config symbols.
+fn connections(
+    state: &Arc<Mutex<TickerState>>,
+    config: &Config,
+) -> Vec<(Arc<dyn PriceSource>, PriceSink)> {
+    let mut connections = Vec::new();
+    for (name, coins) in coins_by_source(config) {
+        let Some(source) = build(name, config) else {
+            let symbols: Vec<&str> = coins.iter().map(|c| c.symbol.as_str()).collect();
+            eprintln!("Warning: Unknown price source '{}' for {}", name, symbols.join(", "));
+            continue;
+        };
+
+        let sink = PriceSink::new(Arc::clone(state), name, symbol_map(source.as_ref(), &coins));
+        connections.push((Arc::from(source), sink));
+    }
+
+    connections
+}
+
+/// Reload the coin list whenever the process receives SIGHUP. Running
+/// sources pick up their new symbols through their sinks; sources that
+/// weren't connected before need a restart.
+async fn reload_on_hangup(
+    state: Arc<Mutex<TickerState>>,
+    sources: Vec<(Arc<dyn PriceSource>, Arc<PriceSink>)>,
+) {
+    let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+        eprintln!("Warning: Cannot listen for SIGHUP, config reload disabled");
+        return;
+    };
+
+    while hangup.recv().await.is_some() {
+        // A broken file keeps the current coins rather than reverting to defaults
+        let config = match Config::try_load() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: {}; keeping the current config", e);
+                continue;
+            }
+        };
+        let groups = coins_by_source(&config);
+
+        for (source, sink) in &sources {
+            let coins = groups.get(source.name()).map_or(&[][..], Vec::as_slice);
+            sink.set_symbols(symbol_map(source.as_ref(), coins));
+        }
+        for name in groups.keys() {
+            if !sources.iter().any(|(source, _)| source.name() == *name) {
+                eprintln!("Warning: Source '{}' was added; restart to connect to it", name);
+            }
+        }
+
+        if let Ok(mut state) = state.lock() {
+            state.set_coins(&config.coins);
+        }
+        eprintln!("Reloaded {} coins from config", config.coins.len());
+    }
+}
+
+/// Streaming loop for a single source with automatic reconnection.
+///
+/// Reconnects back off exponentially with jitter, capped at `BACKOFF_MAX`,
+/// and start over from `BACKOFF_INITIAL` after a healthy session. Each
+/// attempt subscribes to the sink's current symbols.
+async fn stream_forever(source: Arc<dyn PriceSource>, sink: Arc<PriceSink>) {
+    let mut backoff = BACKOFF_INITIAL;
+
+    loop {
+        // A source left without coins by a reload idles until it gets some
+        let mut changes = sink.watch_symbols();
+        let symbols = sink.symbols();
+        if symbols.is_empty() {
+            if changes.changed().await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        sink.set_connection(ConnectionState::Connecting);
+        let started = Instant::now();
+
+        match source.stream(&symbols, &sink).await {
+            // Not a failure, so neither wait nor grow the backoff
+            Ok(SessionEnd::SymbolsChanged) => continue,
+            Ok(SessionEnd::Closed) => eprintln!("{}: connection closed", source.name()),
+            Err(e) => eprintln!("{} error: {:?}", source.name(), e),
+        }
+
+        if started.elapsed() >= HEALTHY_SESSION {
+            backoff = BACKOFF_INITIAL;
+        }
+        let delay = jitter(backoff);
+        backoff = (backoff * 2).min(BACKOFF_MAX);
+
+        sink.set_connection(ConnectionState::Retrying { until: Instant::now() + delay });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Randomize a delay to between half and all of `delay`, so sources (and
+/// other clients) that failed together don't reconnect in lockstep.
+fn jitter(delay: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let fraction = 0.5 + (random % 1000) as f64 / 2000.0;
+    delay.mul_f64(fraction)
+}
+
+/// Deserialize a number that the exchange sends as a JSON string.
+pub fn string_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.and_then(|v| v.parse().ok()))
+}
+
+/// Read a number from a JSON value that may encode it as a string.
+pub fn json_f64(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+/// Derive the 24h open from the current price and a 24h change, given either
+/// as a percentage or as a price delta.
+pub fn open_from_change(price: f64, change: f64, percent: bool) -> Option<f64> {
+    let open = if percent {
+        price / (1.0 + change / 100.0)
+    } else {
+        price - change
+    };
+    (open > 0.0 && open.is_finite()).then_some(open)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Ends its first session because the symbols changed, then stays up.
+    struct Reconnecting {
+        sessions: AtomicUsize,
+    }
+
+    impl PriceSource for Reconnecting {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn stream<'a>(
+            &'a self,
+            _symbols: &'a [String],
+            _sink: &'a PriceSink,
+        ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+            Box::pin(async move {
+                if self.sessions.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Ok(SessionEnd::SymbolsChanged);
+                }
+                std::future::pending().await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn symbol_change_reconnects_without_backoff() {
+        let state = Arc::new(Mutex::new(TickerState::new(&Config::default(), false)));
+        let symbols = HashMap::from([("BTC/USD".to_string(), "BTC/USD".to_string())]);
+        let sink = Arc::new(PriceSink::new(state, "test", symbols));
+        let source = Arc::new(Reconnecting { sessions: AtomicUsize::new(0) });
+        let task = tokio::spawn(stream_forever(source.clone(), sink));
+
+        // A retry would wait at least half of BACKOFF_INITIAL
+        tokio::time::sleep(BACKOFF_INITIAL / 4).await;
+        assert_eq!(source.sessions.load(Ordering::SeqCst), 2);
+        task.abort();
+    }
+}
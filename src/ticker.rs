@@ -1,7 +1,12 @@
 //! Ticker state and display segment management.
 
-use crate::config::{CoinConfig, Config};
-use std::collections::HashMap;
+use crate::alert::{CoinAlerts, Notification};
+use crate::cache;
+use crate::config::{Aggregate, CoinConfig, Config};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
 
 /// Price movement direction for coloring.
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -11,79 +16,585 @@ pub enum Direction {
     Neutral,
 }
 
+/// What a segment shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// A single coin's price, or why it has none.
+    Coin,
+    /// The combined value of every coin with an `amount`.
+    Portfolio,
+    /// Unrealized profit or loss of a coin, or of the whole portfolio.
+    Pnl,
+    /// Connection status, shown while every price is from the cache.
+    Status,
+    Separator,
+}
+
 /// A rendered segment of the ticker display.
 #[derive(Clone)]
 pub struct Segment {
+    pub kind: SegmentKind,
     pub text: String,
     pub direction: Direction,
     pub icon: Option<String>,
+    /// The quote hasn't updated within the staleness threshold.
+    pub stale: bool,
+    /// Text shown when hovering the segment.
+    pub tooltip: Option<String>,
+    /// Candles to draw as a sparkline after the text, oldest first.
+    pub chart: Option<Arc<[Candle]>>,
+    /// Whether the last price change was up or down from the price before
+    /// it, and when it happened, for the renderer's flash.
+    pub tick: Option<(Direction, Instant)>,
+}
+
+impl Segment {
+    fn separator() -> Self {
+        Self {
+            kind: SegmentKind::Separator,
+            text: SEPARATOR.to_string(),
+            direction: Direction::Neutral,
+            icon: None,
+            stale: false,
+            tooltip: None,
+            chart: None,
+            tick: None,
+        }
+    }
+}
+
+/// One OHLC candle. The open isn't kept since sparklines only need the range
+/// and close.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candle {
+    /// Start of the candle's interval, in seconds since the Unix epoch.
+    pub begin: u64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+/// Connection status of a single price source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Live,
+    /// Waiting to reconnect until the given time.
+    Retrying { until: Instant },
+}
+
+/// Latest values reported for a coin by one source.
+#[derive(Clone)]
+pub struct Quote {
+    pub price: f64,
+    pub open_24h: f64,
+    /// Best bid and ask, or 0 if the source doesn't report them.
+    pub bid: f64,
+    pub ask: f64,
+    /// 24h traded volume, used to weight VWAP aggregation.
+    pub volume: f64,
+    /// 24h high, low and volume-weighted average, or 0 if not reported.
+    pub high: f64,
+    pub low: f64,
+    pub vwap: f64,
+    pub updated: Instant,
 }
 
 /// Price data for a single coin.
 #[derive(Clone)]
 pub struct CoinData {
     pub price: f64,
+    /// Displayed price before the latest change, or 0 until it first changes.
+    pub previous_price: f64,
+    /// When the displayed price last changed.
+    pub price_changed: Instant,
     pub open_24h: f64,
+    /// Best bid and ask of the displayed quote, or 0 if unknown.
+    pub bid: f64,
+    pub ask: f64,
+    /// 24h statistics of the displayed quote, or 0 if unknown.
+    pub volume: f64,
+    pub high: f64,
+    pub low: f64,
+    pub vwap: f64,
+    /// When the displayed quote last changed.
+    pub updated: Instant,
+    /// Source the displayed price comes from, or the aggregation mode.
+    pub source: String,
+    /// Latest quote from every source streaming this coin.
+    pub quotes: HashMap<String, Quote>,
+    /// Spread between the highest and lowest aggregated quote, in percent.
+    pub spread: f64,
+    /// Whether `spread` exceeds the coin's `spread_alert` threshold.
+    pub spread_alert: bool,
+    /// Loaded from the price cache and not yet updated by a live source.
+    pub cached: bool,
+}
+
+impl CoinData {
+    /// Change the displayed price, remembering the one it replaces.
+    fn set_price(&mut self, price: f64) {
+        if price != self.price {
+            self.previous_price = self.price;
+            self.price_changed = Instant::now();
+            self.price = price;
+        }
+    }
 }
 
 /// Manages price state and generates display segments.
 pub struct TickerState {
     prices: HashMap<String, CoinData>,
     coins: Vec<CoinConfig>,
+    failover_after: Duration,
+    stale_after: Option<Duration>,
+    stale_glyph: String,
+    format: String,
+    holding_format: String,
+    portfolio_format: String,
+    pnl_format: String,
+    tooltip_format: String,
+    /// Mask holding sizes and values, keeping percentages.
+    privacy: bool,
+    connections: BTreeMap<String, ConnectionState>,
+    /// Why a source refused to stream a symbol, keyed by symbol then source.
+    errors: HashMap<String, BTreeMap<String, String>>,
+    /// Most recent candles per symbol, oldest first, for sparklines.
+    candles: HashMap<String, VecDeque<Candle>>,
+    /// Copy of each symbol's candles taken when they change, shared with
+    /// the segments so rebuilding them and drawing don't copy the buffer.
+    charts: HashMap<String, Arc<[Candle]>>,
+    candle_capacity: usize,
+    /// Price alerts of every coin that has any.
+    alerts: HashMap<String, CoinAlerts>,
+    hysteresis: f64,
+    /// Where fired alerts are delivered; they're only logged without one.
+    notifier: Option<UnboundedSender<Notification>>,
     pub segments: Vec<Segment>,
 }
 
 const SEPARATOR: &str = "     ·     ";
+/// Shown in place of holding sizes and values in privacy mode.
+const MASK: &str = "•••";
 
 impl TickerState {
-    pub fn new(config: &Config) -> Self {
-        Self {
-            prices: HashMap::new(),
+    /// Start from the cached prices when `load_cache` is set. Replays skip
+    /// the cache so they only ever show the recorded prices.
+    pub fn new(config: &Config, load_cache: bool) -> Self {
+        let mut prices = if load_cache { cache::load() } else { HashMap::new() };
+        prices.retain(|symbol, _| config.coins.iter().any(|c| c.symbol == *symbol));
+
+        let mut state = Self {
+            prices,
             coins: config.coins.clone(),
+            failover_after: config.source.failover_after,
+            stale_after: config.source.stale_after,
+            stale_glyph: config.appearance.stale_glyph.clone(),
+            format: config.appearance.format.clone(),
+            holding_format: config.appearance.holding_format.clone(),
+            portfolio_format: config.appearance.portfolio_format.clone(),
+            pnl_format: config.appearance.pnl_format.clone(),
+            privacy: config.appearance.privacy,
+            tooltip_format: config.appearance.tooltip_format.clone(),
+            connections: BTreeMap::new(),
+            errors: HashMap::new(),
+            candles: HashMap::new(),
+            charts: HashMap::new(),
+            candle_capacity: config.sparkline.as_ref().map_or(0, |s| s.capacity()),
+            alerts: config.coins.iter()
+                .filter(|c| !c.alerts.is_empty())
+                .map(|c| (c.symbol.clone(), CoinAlerts::new(&c.alerts)))
+                .collect(),
+            hysteresis: config.alerts.hysteresis,
+            notifier: None,
             segments: Vec::new(),
+        };
+        state.rebuild_segments();
+        state
+    }
+
+    /// Persist the current prices so the next start can show them right away.
+    pub fn save_cache(&self) {
+        cache::save(&self.prices);
+    }
+
+    /// Switch to a new coin list, dropping everything kept for coins that
+    /// were removed and quotes from sources a coin no longer lists.
+    pub fn set_coins(&mut self, coins: &[CoinConfig]) {
+        self.coins = coins.to_vec();
+
+        let listed = |symbol: &str| coins.iter().find(|c| c.symbol == symbol);
+        self.prices.retain(|symbol, data| {
+            let Some(coin) = listed(symbol) else {
+                return false;
+            };
+            data.quotes.retain(|source, _| coin.sources.contains(source));
+            data.cached || !data.quotes.is_empty()
+        });
+        self.errors.retain(|symbol, _| listed(symbol).is_some());
+        self.candles.retain(|symbol, _| listed(symbol).is_some());
+        self.charts.retain(|symbol, _| listed(symbol).is_some());
+
+        // Alerts that didn't change keep their state, so a reload doesn't
+        // repeat notifications
+        self.alerts.retain(|symbol, alerts| {
+            listed(symbol).is_some_and(|coin| coin.alerts == alerts.alerts())
+        });
+        for coin in coins.iter().filter(|c| !c.alerts.is_empty()) {
+            self.alerts.entry(coin.symbol.clone())
+                .or_insert_with(|| CoinAlerts::new(&coin.alerts));
         }
+
+        let symbols: Vec<String> = self.prices.keys().cloned().collect();
+        for symbol in &symbols {
+            self.resolve(symbol);
+        }
+        self.rebuild_segments();
     }
 
-    /// Update the current price for a symbol.
-    pub fn update_price(&mut self, symbol: &str, price: f64) {
-        if let Some(data) = self.prices.get_mut(symbol) {
-            data.price = price;
-        } else {
-            self.prices.insert(symbol.to_string(), CoinData {
-                price,
-                open_24h: price,
-            });
+    /// Switch privacy mode on or off, returning whether it's now on.
+    pub fn toggle_privacy(&mut self) -> bool {
+        self.privacy = !self.privacy;
+        self.rebuild_segments();
+        self.privacy
+    }
+
+    /// Deliver fired alerts to `notifier` from now on.
+    pub fn set_notifier(&mut self, notifier: UnboundedSender<Notification>) {
+        self.notifier = Some(notifier);
+    }
+
+    /// Record the connection status of a price source.
+    pub fn set_connection(&mut self, source: &str, connection: ConnectionState) {
+        self.connections.insert(source.to_string(), connection);
+    }
+
+    /// Text shown in place of the ticker while no prices are available.
+    pub fn status_text(&self) -> String {
+        let now = Instant::now();
+        let live = self.connections.values().any(|c| *c == ConnectionState::Live);
+        let retry = self.connections.values()
+            .filter_map(|c| match c {
+                ConnectionState::Retrying { until } => Some(until.saturating_duration_since(now)),
+                _ => None,
+            })
+            .min();
+
+        match retry {
+            Some(wait) if !live => format!("Retrying in {}s...", wait.as_secs_f64().ceil()),
+            _ if live => "Waiting for prices...".to_string(),
+            _ => "Connecting...".to_string(),
+        }
+    }
+
+    /// Re-evaluate time-dependent state (failover, staleness) for quotes
+    /// that haven't updated. Called periodically by the renderer.
+    pub fn refresh(&mut self) {
+        let symbols: Vec<String> = self.prices.keys().cloned().collect();
+        for symbol in &symbols {
+            self.resolve(symbol);
         }
         self.rebuild_segments();
     }
 
-    /// Set the 24h open price for calculating change percentage.
-    pub fn set_open_price(&mut self, symbol: &str, open: f64) {
-        if let Some(data) = self.prices.get_mut(symbol) {
-            data.open_24h = open;
-        } else {
-            self.prices.insert(symbol.to_string(), CoinData {
-                price: 0.0,
-                open_24h: open,
-            });
+    /// Record that `source` rejected a symbol. Coins without any price are
+    /// shown with the reason instead of being silently left out.
+    pub fn set_error(&mut self, source: &str, symbol: &str, reason: &str) {
+        eprintln!("Warning: {} rejected {}: {}", source, symbol, reason);
+        self.errors.entry(symbol.to_string())
+            .or_default()
+            .insert(source.to_string(), reason.to_string());
+        self.rebuild_segments();
+    }
+
+    /// Update the current price reported by `source` for a symbol.
+    pub fn update_price(&mut self, source: &str, symbol: &str, price: f64) {
+        if let Some(errors) = self.errors.get_mut(symbol) {
+            errors.remove(source);
+        }
+        let quote = self.quote_mut(source, symbol);
+        if quote.price <= 0.0 && quote.open_24h <= 0.0 {
+            quote.open_24h = price;
         }
+        quote.price = price;
+        quote.updated = Instant::now();
+        self.resolve(symbol);
+        self.check_alerts(symbol);
         self.rebuild_segments();
     }
 
-    fn get_change(&self, symbol: &str) -> (String, Direction) {
-        if let Some(data) = self.prices.get(symbol) {
-            if data.open_24h > 0.0 {
-                let change = ((data.price - data.open_24h) / data.open_24h) * 100.0;
-                if change > 0.01 {
-                    return (format!("+{:.1}%▲", change), Direction::Up);
-                } else if change < -0.01 {
-                    return (format!("{:.1}%▼", change), Direction::Down);
-                } else {
-                    return (format!("{:.1}%", change), Direction::Neutral);
-                }
+    /// Evaluate a coin's alerts against its displayed price.
+    fn check_alerts(&mut self, symbol: &str) {
+        let (Some(alerts), Some(data)) = (self.alerts.get_mut(symbol), self.prices.get(symbol))
+        else {
+            return;
+        };
+        let name = self.coins.iter().find(|c| c.symbol == symbol).map_or(symbol, |c| &c.name);
+
+        for body in alerts.check(data.price, self.hysteresis) {
+            let notification = Notification {
+                summary: format!("{} {}", name, Self::format_price(data.price)),
+                body,
+            };
+            eprintln!("Alert: {}: {}", notification.summary, notification.body);
+            if let Some(notifier) = &self.notifier {
+                let _ = notifier.send(notification);
+            }
+        }
+    }
+
+    /// Set the 24h open price reported by `source` for calculating change percentage.
+    pub fn set_open_price(&mut self, source: &str, symbol: &str, open: f64) {
+        let quote = self.quote_mut(source, symbol);
+        quote.open_24h = open;
+        quote.updated = Instant::now();
+        self.resolve(symbol);
+        self.rebuild_segments();
+    }
+
+    /// Set the best bid and ask reported by `source`.
+    pub fn set_book(&mut self, source: &str, symbol: &str, bid: f64, ask: f64) {
+        let quote = self.quote_mut(source, symbol);
+        quote.bid = bid;
+        quote.ask = ask;
+        self.resolve(symbol);
+        self.rebuild_segments();
+    }
+
+    /// Set the 24h high and low reported by `source`.
+    pub fn set_range(&mut self, source: &str, symbol: &str, high: f64, low: f64) {
+        let quote = self.quote_mut(source, symbol);
+        quote.high = high;
+        quote.low = low;
+        self.resolve(symbol);
+        self.rebuild_segments();
+    }
+
+    /// Set the 24h volume-weighted average price reported by `source`.
+    pub fn set_vwap(&mut self, source: &str, symbol: &str, vwap: f64) {
+        let quote = self.quote_mut(source, symbol);
+        quote.vwap = vwap;
+        self.resolve(symbol);
+        self.rebuild_segments();
+    }
+
+    /// Add or update a candle. Updates to the latest candle replace it; the
+    /// oldest candles are dropped once the sparkline window is full.
+    pub fn add_candle(&mut self, symbol: &str, candle: Candle) {
+        if self.candle_capacity == 0 {
+            return;
+        }
+        let candles = self.candles.entry(symbol.to_string()).or_default();
+        match candles.back_mut() {
+            Some(last) if last.begin == candle.begin => *last = candle,
+            Some(last) if last.begin > candle.begin => return,
+            _ => candles.push_back(candle),
+        }
+        while candles.len() > self.candle_capacity {
+            candles.pop_front();
+        }
+        if candles.len() >= 2 {
+            self.charts.insert(symbol.to_string(), candles.iter().copied().collect());
+        }
+        self.rebuild_segments();
+    }
+
+    /// Set the 24h volume reported by `source`, used for VWAP aggregation.
+    pub fn set_volume(&mut self, source: &str, symbol: &str, volume: f64) {
+        let quote = self.quote_mut(source, symbol);
+        quote.volume = volume;
+        self.resolve(symbol);
+        self.rebuild_segments();
+    }
+
+    fn quote_mut(&mut self, source: &str, symbol: &str) -> &mut Quote {
+        let data = self.prices.entry(symbol.to_string()).or_insert_with(|| CoinData {
+            price: 0.0,
+            previous_price: 0.0,
+            price_changed: Instant::now(),
+            open_24h: 0.0,
+            bid: 0.0,
+            ask: 0.0,
+            volume: 0.0,
+            high: 0.0,
+            low: 0.0,
+            vwap: 0.0,
+            updated: Instant::now(),
+            source: source.to_string(),
+            quotes: HashMap::new(),
+            spread: 0.0,
+            spread_alert: false,
+            cached: false,
+        });
+        data.quotes.entry(source.to_string()).or_insert_with(|| Quote {
+            price: 0.0,
+            open_24h: 0.0,
+            bid: 0.0,
+            ask: 0.0,
+            volume: 0.0,
+            high: 0.0,
+            low: 0.0,
+            vwap: 0.0,
+            updated: Instant::now(),
+        })
+    }
+
+    /// Recompute the displayed price from the coin's quotes.
+    fn resolve(&mut self, symbol: &str) {
+        let aggregate = self.coins.iter()
+            .find(|c| c.symbol == symbol)
+            .map_or(Aggregate::Failover, |c| c.aggregate);
+
+        match aggregate {
+            Aggregate::Failover => self.select_source(symbol),
+            Aggregate::Median | Aggregate::Vwap => self.aggregate(symbol, aggregate),
+        }
+    }
+
+    /// Pick the displayed quote: the first source in the coin's preference
+    /// order that updated within the failover window, or failing that the
+    /// most recently updated one.
+    fn select_source(&mut self, symbol: &str) {
+        let Some(data) = self.prices.get_mut(symbol) else {
+            return;
+        };
+        let preferred = self.coins.iter()
+            .find(|c| c.symbol == symbol)
+            .map_or(&[][..], |c| c.sources.as_slice());
+
+        let now = Instant::now();
+        let usable = data.quotes.iter().filter(|(_, q)| q.price > 0.0);
+        let fresh = preferred.iter().find(|name| {
+            data.quotes.get(*name).is_some_and(|q| {
+                q.price > 0.0 && now.duration_since(q.updated) < self.failover_after
+            })
+        });
+        let chosen = match fresh {
+            Some(name) => data.quotes.get_key_value(name),
+            None => usable.max_by_key(|(_, q)| q.updated),
+        };
+
+        if let Some((name, quote)) = chosen.map(|(n, q)| (n.clone(), q.clone())) {
+            if data.source != name && data.price > 0.0 && !data.cached {
+                eprintln!("{}: switching from {} to {}", symbol, data.source, name);
             }
+            data.set_price(quote.price);
+            data.open_24h = quote.open_24h;
+            data.bid = quote.bid;
+            data.ask = quote.ask;
+            data.volume = quote.volume;
+            data.high = quote.high;
+            data.low = quote.low;
+            data.vwap = quote.vwap;
+            data.updated = quote.updated;
+            data.source = name;
+            data.cached = false;
+        }
+    }
+
+    /// Combine every quote updated within the failover window into a median
+    /// or volume-weighted price. Falls back to single-source selection while
+    /// fewer than two sources are fresh.
+    fn aggregate(&mut self, symbol: &str, mode: Aggregate) {
+        let Some(data) = self.prices.get_mut(symbol) else {
+            return;
+        };
+        let spread_alert = self.coins.iter()
+            .find(|c| c.symbol == symbol)
+            .and_then(|c| c.spread_alert);
+
+        let now = Instant::now();
+        let fresh: Vec<&Quote> = data.quotes.values()
+            .filter(|q| q.price > 0.0 && now.duration_since(q.updated) < self.failover_after)
+            .collect();
+        if fresh.len() < 2 {
+            data.spread = 0.0;
+            data.spread_alert = false;
+            self.select_source(symbol);
+            return;
+        }
+
+        let total_volume: f64 = fresh.iter().map(|q| q.volume).sum();
+        let (price, open) = if mode == Aggregate::Vwap && total_volume > 0.0 {
+            (
+                fresh.iter().map(|q| q.price * q.volume).sum::<f64>() / total_volume,
+                fresh.iter().map(|q| q.open_24h * q.volume).sum::<f64>() / total_volume,
+            )
+        } else {
+            (
+                median(fresh.iter().map(|q| q.price).collect()),
+                median(fresh.iter().map(|q| q.open_24h).collect()),
+            )
+        };
+
+        // Book prices and 24h statistics are only combined across the
+        // sources that report them
+        let reported = |value: fn(&Quote) -> f64| {
+            let values: Vec<f64> = fresh.iter().map(|q| value(q)).filter(|v| *v > 0.0).collect();
+            if values.is_empty() { 0.0 } else { median(values) }
+        };
+        let (bid, ask) = (reported(|q| q.bid), reported(|q| q.ask));
+        let (high_24h, low_24h) = (reported(|q| q.high), reported(|q| q.low));
+        let vwap = reported(|q| q.vwap);
+
+        let high = fresh.iter().map(|q| q.price).fold(f64::MIN, f64::max);
+        let low = fresh.iter().map(|q| q.price).fold(f64::MAX, f64::min);
+        let spread = (high - low) / price * 100.0;
+        let alert = spread_alert.is_some_and(|threshold| spread > threshold);
+        if alert && !data.spread_alert {
+            eprintln!("{}: {:.2}% spread across {} sources", symbol, spread, fresh.len());
+        }
+
+        data.open_24h = open;
+        data.bid = bid;
+        data.ask = ask;
+        data.volume = total_volume;
+        data.high = high_24h;
+        data.low = low_24h;
+        data.vwap = vwap;
+        data.updated = fresh.iter().map(|q| q.updated).max().unwrap_or(now);
+        data.set_price(price);
+        data.source = match mode {
+            Aggregate::Vwap => "vwap".to_string(),
+            _ => "median".to_string(),
+        };
+        data.spread = spread;
+        data.spread_alert = alert;
+        data.cached = false;
+    }
+
+    fn get_change(&self, symbol: &str) -> (String, Direction) {
+        match self.prices.get(symbol) {
+            Some(data) => Self::describe_change(data.price, data.open_24h),
+            None => ("--".to_string(), Direction::Neutral),
+        }
+    }
+
+    /// Percent change from `open` to `current`, with its arrow and direction.
+    fn describe_change(current: f64, open: f64) -> (String, Direction) {
+        if open <= 0.0 {
+            return ("--".to_string(), Direction::Neutral);
+        }
+        let change = ((current - open) / open) * 100.0;
+        if change > 0.01 {
+            (format!("+{:.1}%▲", change), Direction::Up)
+        } else if change < -0.01 {
+            (format!("{:.1}%▼", change), Direction::Down)
+        } else {
+            (format!("{:.1}%", change), Direction::Neutral)
+        }
+    }
+
+    /// Format a price, or its abbreviated form for `:short` placeholders.
+    /// Values that aren't known show as "--".
+    fn format_value(value: f64, short: bool) -> String {
+        if value <= 0.0 {
+            "--".to_string()
+        } else if short && value >= 1000.0 {
+            format!("${}", Self::format_compact(value))
+        } else {
+            Self::format_price(value)
         }
-        ("--".to_string(), Direction::Neutral)
     }
 
     fn format_price(price: f64) -> String {
@@ -98,35 +609,444 @@ impl TickerState {
         }
     }
 
+    /// Abbreviate large numbers with a k/M/B suffix, e.g. 67400 as "67k".
+    fn format_compact(value: f64) -> String {
+        let (scaled, suffix) = if value >= 1e9 {
+            (value / 1e9, "B")
+        } else if value >= 1e6 {
+            (value / 1e6, "M")
+        } else if value >= 1e3 {
+            (value / 1e3, "k")
+        } else {
+            return format!("{:.2}", value);
+        };
+        if scaled >= 10.0 {
+            format!("{:.0}{}", scaled, suffix)
+        } else {
+            format!("{:.1}{}", scaled, suffix)
+        }
+    }
+
+    /// Format a profit or loss with its sign, e.g. "+$1200" or "-$35.50".
+    fn format_signed(value: f64) -> String {
+        let sign = if value < 0.0 { "-" } else { "+" };
+        format!("{}{}", sign, Self::format_price(value.abs()))
+    }
+
+    /// Unrealized profit or loss of a coin's holding against its cost basis,
+    /// or `None` without both an amount and a cost basis.
+    fn pnl(coin: &CoinConfig, data: &CoinData) -> Option<f64> {
+        Some(coin.amount? * (data.price - coin.cost_basis?))
+    }
+
+    /// Expand a format template's placeholders for one coin. Prices can be
+    /// abbreviated with `:short`, e.g. `{high:short}`; values the source
+    /// doesn't report show as "--". In privacy mode the holding's size and
+    /// worth are masked while percentages stay visible.
+    fn expand(
+        template: &str,
+        coin: &CoinConfig,
+        data: &CoinData,
+        change: &str,
+        private: bool,
+    ) -> String {
+        let spread = if data.bid > 0.0 && data.ask > 0.0 { data.ask - data.bid } else { 0.0 };
+        let prices = [
+            ("price", data.price),
+            ("bid", data.bid),
+            ("ask", data.ask),
+            ("spread", spread),
+            ("high", data.high),
+            ("low", data.low),
+            ("vwap", data.vwap),
+        ];
+
+        let volume = if data.volume > 0.0 {
+            Self::format_compact(data.volume)
+        } else {
+            "--".to_string()
+        };
+        let mask = |text: String| if private && text != "--" { MASK.to_string() } else { text };
+        let value = coin.amount.unwrap_or(0.0) * data.price;
+        let pnl = Self::pnl(coin, data).map_or("--".to_string(), Self::format_signed);
+        let pnl_pct = coin.cost_basis
+            .map_or("--".to_string(), |cost| Self::describe_change(data.price, cost).0);
+
+        let mut text = template
+            .replace("{name}", &coin.name)
+            .replace("{change}", change)
+            .replace("{volume}", &volume)
+            .replace("{volume:short}", &volume)
+            .replace("{amount}", &mask(coin.amount.map_or("--".to_string(), |a| a.to_string())))
+            .replace("{value}", &mask(Self::format_value(value, false)))
+            .replace("{value:short}", &mask(Self::format_value(value, true)))
+            .replace("{pnl}", &mask(pnl))
+            .replace("{pnl_pct}", &pnl_pct);
+
+        for (key, value) in prices {
+            text = text
+                .replace(&format!("{{{}}}", key), &Self::format_value(value, false))
+                .replace(&format!("{{{}:short}}", key), &Self::format_value(value, true));
+        }
+
+        text
+    }
+
+    /// The combined value and 24h change of every coin with an `amount`,
+    /// followed by the total unrealized P&L of those with a `cost_basis`.
+    /// Empty while none of them has a price.
+    fn portfolio_segments(&self) -> Vec<Segment> {
+        let now = Instant::now();
+        let mut value = 0.0;
+        let mut open = 0.0;
+        let mut pnl = 0.0;
+        let mut cost = 0.0;
+        let mut stale = false;
+        let mut positions = Vec::new();
+
+        for coin in &self.coins {
+            let (Some(amount), Some(data)) = (coin.amount, self.prices.get(&coin.symbol)) else {
+                continue;
+            };
+            if data.price <= 0.0 {
+                continue;
+            }
+            // Without an open price the position counts as unchanged
+            let start = if data.open_24h > 0.0 { data.open_24h } else { data.price };
+            value += amount * data.price;
+            open += amount * start;
+            if let Some(cost_basis) = coin.cost_basis {
+                pnl += amount * (data.price - cost_basis);
+                cost += amount * cost_basis;
+            }
+            stale |= data.cached || self.stale_after
+                .is_some_and(|after| now.duration_since(data.updated) >= after);
+            positions.push((&coin.name, amount * data.price));
+        }
+        if positions.is_empty() {
+            return Vec::new();
+        }
+
+        // Privacy mode lists each holding's share of the total instead
+        let tooltip = (!self.tooltip_format.is_empty()).then(|| {
+            positions.iter()
+                .map(|(name, worth)| match self.privacy {
+                    true => format!("{} {:.1}%", name, worth / value * 100.0),
+                    false => format!("{} {}", name, Self::format_price(*worth)),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let masked = |text: String| if self.privacy { MASK.to_string() } else { text };
+
+        let (change, direction) = Self::describe_change(value, open);
+        let text = self.portfolio_format
+            .replace("{change}", &change)
+            .replace("{value}", &masked(Self::format_value(value, false)))
+            .replace("{value:short}", &masked(Self::format_value(value, true)));
+        let mut segments = vec![Segment {
+            kind: SegmentKind::Portfolio,
+            text,
+            direction,
+            icon: None,
+            stale,
+            tooltip: tooltip.clone(),
+            chart: None,
+            tick: None,
+        }];
+
+        if cost > 0.0 && !self.pnl_format.is_empty() {
+            let (pnl_pct, direction) = Self::describe_change(cost + pnl, cost);
+            let text = self.pnl_format
+                .replace("{pnl}", &masked(Self::format_signed(pnl)))
+                .replace("{pnl_pct}", &pnl_pct);
+            segments.push(Segment {
+                kind: SegmentKind::Pnl,
+                text: format!(" {}", text),
+                direction,
+                icon: None,
+                stale,
+                tooltip,
+                chart: None,
+                tick: None,
+            });
+        }
+
+        segments
+    }
+
     fn rebuild_segments(&mut self) {
         self.segments.clear();
 
-        let active_count = self.coins.iter()
-            .filter(|c| self.prices.get(&c.symbol).map_or(false, |d| d.price > 0.0))
-            .count();
+        let has_price = |c: &CoinConfig| self.prices.get(&c.symbol).is_some_and(|d| d.price > 0.0);
+        let has_error = |c: &CoinConfig| self.errors.get(&c.symbol).is_some_and(|e| !e.is_empty());
+        let portfolio = self.portfolio_segments();
+        let active_count = self.coins.iter().filter(|c| has_price(c) || has_error(c)).count()
+            + usize::from(!portfolio.is_empty());
+        let now = Instant::now();
+
+        // Cached prices would otherwise hide that no source is live yet
+        let shown: Vec<&CoinData> = self.prices.values().filter(|d| d.price > 0.0).collect();
+        if !shown.is_empty() && shown.iter().all(|d| d.cached) {
+            self.segments.push(Segment {
+                kind: SegmentKind::Status,
+                text: self.status_text(),
+                direction: Direction::Neutral,
+                icon: None,
+                stale: false,
+                tooltip: None,
+                chart: None,
+                tick: None,
+            });
+            self.segments.push(Segment::separator());
+        }
+
+        if !portfolio.is_empty() {
+            self.segments.extend(portfolio);
+            if active_count > 1 {
+                self.segments.push(Segment::separator());
+            }
+        }
 
         for coin in &self.coins {
+            if !has_price(coin) {
+                let Some((source, reason)) = self.errors.get(&coin.symbol)
+                    .and_then(|e| e.iter().next())
+                else {
+                    continue;
+                };
+                self.segments.push(Segment {
+                    kind: SegmentKind::Coin,
+                    text: format!("{} ⚠ {}: {}", coin.name, source, reason),
+                    direction: Direction::Neutral,
+                    icon: Some(coin.icon.clone()),
+                    stale: false,
+                    tooltip: None,
+                    chart: None,
+                    tick: None,
+                });
+                if active_count > 1 {
+                    self.segments.push(Segment::separator());
+                }
+                continue;
+            }
+
             if let Some(data) = self.prices.get(&coin.symbol) {
                 if data.price <= 0.0 {
                     continue;
                 }
                 let (change_str, direction) = self.get_change(&coin.symbol);
-                let price_str = Self::format_price(data.price);
+                let format = match coin.amount {
+                    Some(_) => &self.holding_format,
+                    None => &self.format,
+                };
+                let mut text = Self::expand(format, coin, data, &change_str, self.privacy);
+                let tooltip = (!self.tooltip_format.is_empty()).then(|| {
+                    Self::expand(&self.tooltip_format, coin, data, &change_str, self.privacy)
+                });
+                let tick = (data.previous_price > 0.0).then(|| {
+                    let direction = if data.price > data.previous_price {
+                        Direction::Up
+                    } else {
+                        Direction::Down
+                    };
+                    (direction, data.price_changed)
+                });
+                let chart = self.charts.get(&coin.symbol).cloned();
+
+                // Show which exchange is live when the coin can fail over
+                if coin.sources.len() > 1 {
+                    text.push_str(&format!(" [{}]", data.source));
+                }
+                if data.spread_alert {
+                    text.push_str(&format!(" ⚠{:.1}%", data.spread));
+                }
+
+                let stale = data.cached || self.stale_after
+                    .is_some_and(|after| now.duration_since(data.updated) >= after);
+                if stale && !self.stale_glyph.is_empty() {
+                    text.push_str(&format!(" {}", self.stale_glyph));
+                }
+
+                // Unrealized P&L follows the price, colored by its own sign
+                let pnl = coin.cost_basis.filter(|_| !self.pnl_format.is_empty()).map(|cost| {
+                    let pnl_text =
+                        Self::expand(&self.pnl_format, coin, data, &change_str, self.privacy);
+                    Segment {
+                        kind: SegmentKind::Pnl,
+                        text: format!(" {}", pnl_text),
+                        direction: Self::describe_change(data.price, cost).1,
+                        icon: None,
+                        stale,
+                        tooltip: tooltip.clone(),
+                        chart: None,
+                        tick: None,
+                    }
+                });
 
                 self.segments.push(Segment {
-                    text: format!("{} {}", price_str, change_str),
+                    kind: SegmentKind::Coin,
+                    text,
                     direction,
                     icon: Some(coin.icon.clone()),
+                    stale,
+                    tooltip,
+                    chart,
+                    tick,
                 });
+                self.segments.extend(pnl);
 
                 if active_count > 1 {
-                    self.segments.push(Segment {
-                        text: SEPARATOR.to_string(),
-                        direction: Direction::Neutral,
-                        icon: None,
-                    });
+                    self.segments.push(Segment::separator());
                 }
             }
         }
     }
 }
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BTC: &str = "BTC/USD";
+
+    /// State for a single BTC coin streamed from `sources`.
+    fn state(sources: &[&str], aggregate: Aggregate, spread_alert: Option<f64>) -> TickerState {
+        let mut config = Config::default();
+        config.coins.truncate(1);
+        let coin = &mut config.coins[0];
+        coin.sources = sources.iter().map(|s| s.to_string()).collect();
+        coin.aggregate = aggregate;
+        coin.spread_alert = spread_alert;
+        TickerState::new(&config, false)
+    }
+
+    /// Backdate the last update from `source` by `secs` seconds.
+    fn age(state: &mut TickerState, source: &str, secs: u64) {
+        let quote = state.prices.get_mut(BTC).unwrap().quotes.get_mut(source).unwrap();
+        quote.updated -= Duration::from_secs(secs);
+    }
+
+    fn shown(state: &TickerState) -> (f64, &str) {
+        let data = &state.prices[BTC];
+        (data.price, data.source.as_str())
+    }
+
+    #[test]
+    fn failover_prefers_sources_in_order() {
+        let mut state = state(&["kraken", "coinbase"], Aggregate::Failover, None);
+        state.update_price("kraken", BTC, 101.0);
+        state.update_price("coinbase", BTC, 100.0);
+        assert_eq!(shown(&state), (101.0, "kraken"));
+    }
+
+    #[test]
+    fn failover_switches_away_and_back() {
+        let mut state = state(&["kraken", "coinbase"], Aggregate::Failover, None);
+        state.update_price("kraken", BTC, 101.0);
+        state.update_price("coinbase", BTC, 100.0);
+
+        // Kraken goes quiet for longer than failover_after
+        age(&mut state, "kraken", 61);
+        state.refresh();
+        assert_eq!(shown(&state), (100.0, "coinbase"));
+
+        state.update_price("kraken", BTC, 102.0);
+        assert_eq!(shown(&state), (102.0, "kraken"));
+    }
+
+    #[test]
+    fn failover_without_fresh_sources_shows_the_latest() {
+        let mut state = state(&["kraken", "coinbase"], Aggregate::Failover, None);
+        state.update_price("kraken", BTC, 101.0);
+        state.update_price("coinbase", BTC, 100.0);
+        age(&mut state, "kraken", 120);
+        age(&mut state, "coinbase", 90);
+        state.refresh();
+        assert_eq!(shown(&state), (100.0, "coinbase"));
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(vec![110.0, 100.0, 102.0]), 102.0);
+        assert_eq!(median(vec![104.0, 100.0]), 102.0);
+        assert_eq!(median(vec![7.0]), 7.0);
+    }
+
+    #[test]
+    fn aggregate_median_with_spread_alert() {
+        let sources = ["kraken", "coinbase", "binance"];
+        let mut state = state(&sources, Aggregate::Median, Some(5.0));
+        state.update_price("kraken", BTC, 100.0);
+        state.update_price("coinbase", BTC, 110.0);
+        state.update_price("binance", BTC, 102.0);
+        assert_eq!(shown(&state), (102.0, "median"));
+        // (110 - 100) / 102 is about 9.8%
+        let data = &state.prices[BTC];
+        assert!((data.spread - 9.8).abs() < 0.01, "{}", data.spread);
+        assert!(data.spread_alert);
+
+        // Back within the threshold
+        state.update_price("coinbase", BTC, 103.0);
+        assert_eq!(shown(&state), (102.0, "median"));
+        assert!(!state.prices[BTC].spread_alert);
+    }
+
+    #[test]
+    fn spread_alert_needs_the_threshold_exceeded() {
+        let mut within = state(&["kraken", "coinbase"], Aggregate::Median, Some(20.0));
+        let mut unset = state(&["kraken", "coinbase"], Aggregate::Median, None);
+        for state in [&mut within, &mut unset] {
+            state.update_price("kraken", BTC, 100.0);
+            state.update_price("coinbase", BTC, 110.0);
+            assert_eq!(shown(state), (105.0, "median"));
+            assert!(!state.prices[BTC].spread_alert);
+        }
+    }
+
+    #[test]
+    fn aggregate_vwap_weights_by_volume() {
+        let mut state = state(&["kraken", "coinbase"], Aggregate::Vwap, None);
+        state.set_volume("kraken", BTC, 3.0);
+        state.set_volume("coinbase", BTC, 1.0);
+        state.update_price("kraken", BTC, 100.0);
+        state.update_price("coinbase", BTC, 110.0);
+        assert_eq!(shown(&state), (102.5, "vwap"));
+        assert_eq!(state.prices[BTC].volume, 4.0);
+    }
+
+    #[test]
+    fn aggregate_vwap_without_volume_uses_the_median() {
+        let mut state = state(&["kraken", "coinbase"], Aggregate::Vwap, None);
+        state.update_price("kraken", BTC, 100.0);
+        state.update_price("coinbase", BTC, 110.0);
+        assert_eq!(shown(&state), (105.0, "vwap"));
+    }
+
+    #[test]
+    fn aggregate_with_one_fresh_source_shows_it_alone() {
+        let sources = ["kraken", "coinbase", "binance"];
+        let mut state = state(&sources, Aggregate::Median, Some(1.0));
+        state.update_price("kraken", BTC, 100.0);
+        state.update_price("coinbase", BTC, 110.0);
+        state.update_price("binance", BTC, 120.0);
+        assert!(state.prices[BTC].spread_alert);
+
+        age(&mut state, "kraken", 61);
+        age(&mut state, "binance", 61);
+        state.refresh();
+        assert_eq!(shown(&state), (110.0, "coinbase"));
+        assert_eq!(state.prices[BTC].spread, 0.0);
+        assert!(!state.prices[BTC].spread_alert);
+    }
+}
@@ -1,13 +1,21 @@
 //! WebSocket connection to Kraken for real-time price updates.
+//!
+//! Also hosts the connection loop shared by the other WebSocket sources.
 
-use crate::config::Config;
-use crate::ticker::TickerState;
+use crate::proxy::Proxy;
+use crate::source::{PriceEvent, PriceSink, PriceSource, SessionEnd, SourceError};
+use crate::ticker::Candle;
+use futures_util::future::BoxFuture;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{
+    client_async_tls_with_config, connect_async_tls_with_config, tungstenite::Message, Connector,
+};
 
-const KRAKEN_WS: &str = "wss://ws.kraken.com/v2";
+pub const KRAKEN_WS: &str = "wss://ws.kraken.com/v2";
 
 #[derive(Serialize)]
 struct SubscribeMessage {
@@ -19,85 +27,418 @@ struct SubscribeMessage {
 struct SubscribeParams {
     channel: String,
     symbol: Vec<String>,
-    snapshot: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<bool>,
+    /// Candle length in minutes, for the `ohlc` channel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u32>,
 }
 
 #[derive(Deserialize)]
 struct WsMessage {
     channel: Option<String>,
-    data: Option<Vec<TickerData>>,
+    /// Parsed according to `channel`.
+    data: Option<serde_json::Value>,
+    /// Set on acknowledgements to requests such as `subscribe`.
+    method: Option<String>,
+    success: Option<bool>,
+    error: Option<String>,
+    symbol: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct TickerData {
     symbol: Option<String>,
     last: Option<f64>,
+    bid: Option<f64>,
+    ask: Option<f64>,
     change: Option<f64>,
+    volume: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    vwap: Option<f64>,
 }
 
-/// Main WebSocket loop with automatic reconnection.
-#[tokio::main]
-pub async fn run(state: &Arc<Mutex<TickerState>>, config: &Config) {
-    let symbols: Vec<String> = config.coins.iter()
-        .map(|c| c.symbol.clone())
-        .collect();
+#[derive(Deserialize)]
+struct OhlcData {
+    symbol: Option<String>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+    interval_begin: Option<String>,
+}
 
-    loop {
-        if let Err(e) = connect_and_stream(state, &symbols).await {
-            eprintln!("WebSocket error: {:?}", e);
+/// Where and how a WebSocket source connects.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub url: String,
+    /// Reconnect when no frame at all arrives for this long; `None` disables.
+    pub watchdog: Option<Duration>,
+    /// Tunnel the connection through this proxy.
+    pub proxy: Option<Proxy>,
+    /// Custom TLS trust settings; `None` uses the system roots.
+    pub tls: Option<native_tls::TlsConnector>,
+}
+
+/// Kraken v2 `ticker` channel, plus `ohlc` candles when sparklines are on.
+pub struct KrakenSource {
+    endpoint: Endpoint,
+    candle_interval: Option<u32>,
+}
+
+impl KrakenSource {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint, candle_interval: None }
+    }
+
+    /// Also subscribe to OHLC candles of the given length in minutes.
+    pub fn with_candles(mut self, interval: u32) -> Self {
+        self.candle_interval = Some(interval);
+        self
+    }
+
+    /// `subscribe` or `unsubscribe` requests covering every channel in use.
+    fn requests(&self, method: &str, symbols: &[String]) -> Vec<String> {
+        let mut channels = vec![("ticker", None)];
+        if let Some(interval) = self.candle_interval {
+            channels.push(("ohlc", Some(interval)));
+        }
+
+        channels.into_iter()
+            .filter_map(|(channel, interval)| {
+                serde_json::to_string(&SubscribeMessage {
+                    method: method.to_string(),
+                    params: SubscribeParams {
+                        channel: channel.to_string(),
+                        symbol: symbols.to_vec(),
+                        snapshot: (method == "subscribe").then_some(true),
+                        interval,
+                    },
+                })
+                .ok()
+            })
+            .collect()
+    }
+}
+
+impl PriceSource for KrakenSource {
+    fn name(&self) -> &str {
+        "kraken"
+    }
+
+    fn parse(&self, frame: &str) -> Vec<PriceEvent> {
+        parse_ticker(frame)
+    }
+
+    fn stream<'a>(
+        &'a self,
+        symbols: &'a [String],
+        sink: &'a PriceSink,
+    ) -> BoxFuture<'a, Result<SessionEnd, SourceError>> {
+        Box::pin(async move {
+            let subscribe = self.requests("subscribe", symbols);
+            connect_and_stream(&self.endpoint, symbols, subscribe, sink, self).await
+        })
+    }
+
+    fn resubscribe(&self, added: &[String], removed: &[String]) -> Option<Vec<String>> {
+        let mut messages = Vec::new();
+        if !removed.is_empty() {
+            messages.extend(self.requests("unsubscribe", removed));
+        }
+        if !added.is_empty() {
+            messages.extend(self.requests("subscribe", added));
         }
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        Some(messages)
     }
 }
 
-async fn connect_and_stream(
-    state: &Arc<Mutex<TickerState>>,
+/// Connect to the endpoint, send each subscribe message and feed every text
+/// frame through the source's [`PriceSource::parse`] into the sink until the
+/// socket closes. Frames are handed to the sink's recorder first.
+///
+/// `symbols` are the native symbols the subscribe messages cover. When the
+/// sink's symbols change, the difference is applied with
+/// [`PriceSource::resubscribe`], or the session ends with
+/// [`SessionEnd::SymbolsChanged`] so the caller reconnects with the new list.
+///
+/// Any frame, including heartbeats and pongs, counts as a sign of life. After
+/// half the watchdog window of silence a ping is sent; if the window passes
+/// without a reply the connection is treated as dead.
+pub async fn connect_and_stream(
+    endpoint: &Endpoint,
     symbols: &[String],
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let (ws_stream, _) = connect_async(KRAKEN_WS).await?;
-    let (mut write, mut read) = ws_stream.split();
+    subscribe: Vec<String>,
+    sink: &PriceSink,
+    source: &dyn PriceSource,
+) -> Result<SessionEnd, SourceError> {
+    let mut changes = sink.watch_symbols();
+    // Catch changes made since `symbols` was read
+    changes.mark_changed();
+    let mut subscribed: HashSet<String> = symbols.iter().cloned().collect();
+
+    let request = endpoint.url.as_str().into_client_request()?;
+    let host = request.uri().host().unwrap_or_default().to_string();
+    let port = request.uri().port_u16()
+        .unwrap_or(if request.uri().scheme_str() == Some("wss") { 443 } else { 80 });
+
+    let connector = endpoint.tls.clone().map(Connector::NativeTls);
 
-    let subscribe = SubscribeMessage {
-        method: "subscribe".to_string(),
-        params: SubscribeParams {
-            channel: "ticker".to_string(),
-            symbol: symbols.to_vec(),
-            snapshot: true,
-        },
+    let (ws_stream, _) = match endpoint.proxy.as_ref().filter(|p| !p.bypasses(&host)) {
+        Some(proxy) => {
+            let stream = proxy.connect(&host, port).await?;
+            client_async_tls_with_config(request, stream, None, connector).await?
+        }
+        None => connect_async_tls_with_config(request, None, false, connector).await?,
     };
+    let (mut write, mut read) = ws_stream.split();
 
-    write.send(Message::Text(serde_json::to_string(&subscribe)?)).await?;
-
-    while let Some(msg) = read.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    if ws_msg.channel.as_deref() == Some("ticker") {
-                        if let Some(data) = ws_msg.data {
-                            if let Ok(mut state) = state.lock() {
-                                for ticker in data {
-                                    if let (Some(symbol), Some(price)) = (ticker.symbol, ticker.last) {
-                                        state.update_price(&symbol, price);
-                                        if let Some(change) = ticker.change {
-                                            let open = price - change;
-                                            if open > 0.0 {
-                                                state.set_open_price(&symbol, open);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    for message in subscribe {
+        write.send(Message::Text(message)).await?;
+    }
+    sink.connected();
+
+    let mut last_seen = Instant::now();
+    let mut pinged = false;
+    let mut check = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            msg = read.next() => {
+                let Some(msg) = msg else {
+                    break;
+                };
+                last_seen = Instant::now();
+                pinged = false;
+
+                match msg {
+                    Ok(Message::Text(text)) => {
+                        sink.record(&text);
+                        sink.emit(source.parse(&text));
                     }
+                    Ok(Message::Ping(data)) => {
+                        let _ = write.send(Message::Pong(data)).await;
+                    }
+                    Err(e) => return Err(Box::new(e)),
+                    _ => {}
+                }
+            }
+            Ok(()) = changes.changed() => {
+                let wanted: HashSet<String> = changes.borrow_and_update().keys().cloned().collect();
+                let added: Vec<String> = wanted.difference(&subscribed).cloned().collect();
+                let removed: Vec<String> = subscribed.difference(&wanted).cloned().collect();
+                if added.is_empty() && removed.is_empty() {
+                    continue;
+                }
+
+                let Some(messages) = source.resubscribe(&added, &removed) else {
+                    eprintln!("{}: symbols changed, reconnecting", source.name());
+                    return Ok(SessionEnd::SymbolsChanged);
+                };
+                for message in messages {
+                    write.send(Message::Text(message)).await?;
                 }
+                eprintln!(
+                    "{}: subscribed to {} and unsubscribed from {} symbols",
+                    source.name(),
+                    added.len(),
+                    removed.len()
+                );
+                subscribed = wanted;
             }
-            Ok(Message::Ping(data)) => {
-                let _ = write.send(Message::Pong(data)).await;
+            _ = check.tick() => {
+                let Some(watchdog) = endpoint.watchdog else {
+                    continue;
+                };
+                let silent = last_seen.elapsed();
+                if silent >= watchdog {
+                    return Err(format!("no data for {}s", silent.as_secs()).into());
+                }
+                if silent >= watchdog / 2 && !pinged {
+                    write.send(Message::Ping(Vec::new())).await?;
+                    pinged = true;
+                }
+            }
+        }
+    }
+
+    Ok(SessionEnd::Closed)
+}
+
+/// Normalize a Kraken `ticker` or `ohlc` channel message into price events.
+fn parse_ticker(text: &str) -> Vec<PriceEvent> {
+    let mut events = Vec::new();
+
+    let Ok(ws_msg) = serde_json::from_str::<WsMessage>(text) else {
+        return events;
+    };
+
+    // Kraken acknowledges each symbol separately and reports unknown pairs
+    // with `success: false`
+    if ws_msg.method.as_deref() == Some("subscribe") && ws_msg.success == Some(false) {
+        let reason = ws_msg.error.unwrap_or_else(|| "subscription rejected".to_string());
+        match ws_msg.symbol {
+            Some(symbol) => events.push(PriceEvent::Rejected { symbol, reason }),
+            None => eprintln!("Warning: Kraken rejected subscription: {}", reason),
+        }
+        return events;
+    }
+
+    let data = ws_msg.data.unwrap_or_default();
+    match ws_msg.channel.as_deref() {
+        Some("ticker") => {}
+        Some("ohlc") => return parse_candles(data),
+        _ => return events,
+    }
+
+    let tickers: Vec<TickerData> = serde_json::from_value(data).unwrap_or_default();
+    for ticker in tickers {
+        if let (Some(symbol), Some(price)) = (ticker.symbol, ticker.last) {
+            events.push(PriceEvent::Price { symbol: symbol.clone(), price });
+            if let (Some(bid), Some(ask)) = (ticker.bid, ticker.ask) {
+                events.push(PriceEvent::Book { symbol: symbol.clone(), bid, ask });
+            }
+            if let Some(volume) = ticker.volume {
+                events.push(PriceEvent::Volume { symbol: symbol.clone(), volume });
+            }
+            if let (Some(high), Some(low)) = (ticker.high, ticker.low) {
+                events.push(PriceEvent::Range { symbol: symbol.clone(), high, low });
+            }
+            if let Some(vwap) = ticker.vwap {
+                events.push(PriceEvent::Vwap { symbol: symbol.clone(), vwap });
+            }
+            if let Some(change) = ticker.change {
+                let open = price - change;
+                if open > 0.0 {
+                    events.push(PriceEvent::Open { symbol, open });
+                }
             }
-            Err(e) => return Err(Box::new(e)),
-            _ => {}
         }
     }
 
-    Ok(())
+    events
+}
+
+/// Turn `ohlc` channel entries into candle events.
+fn parse_candles(data: serde_json::Value) -> Vec<PriceEvent> {
+    let candles: Vec<OhlcData> = serde_json::from_value(data).unwrap_or_default();
+
+    candles.into_iter()
+        .filter_map(|c| {
+            let begin = c.interval_begin.as_deref().and_then(parse_timestamp)?;
+            Some(PriceEvent::Candle {
+                symbol: c.symbol?,
+                candle: Candle { begin, high: c.high?, low: c.low?, close: c.close? },
+            })
+        })
+        .collect()
+}
+
+/// Parse an RFC 3339 UTC timestamp such as `2024-06-10T14:25:00.000000000Z`
+/// into seconds since the Unix epoch. Fractional seconds are ignored.
+fn parse_timestamp(text: &str) -> Option<u64> {
+    let field = |range: std::ops::Range<usize>| text.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+
+    // Days since the epoch from a civil date (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::ticker::TickerState;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00.000000000Z"), Some(0));
+        assert_eq!(parse_timestamp("2024-02-29T12:00:00Z"), Some(1709208000));
+        assert_eq!(parse_timestamp("2023-12-31T23:59:59Z"), Some(1704067199));
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00Z"), Some(1704067200));
+        assert_eq!(parse_timestamp("2024-01-01"), None);
+    }
+
+    #[test]
+    fn ohlc_snapshot() {
+        let frame = r#"{"channel":"ohlc","type":"snapshot","data":[
+            {"symbol":"BTC/USD","open":66000.0,"high":66100.5,"low":65950.0,"close":66050.0,
+             "trades":12,"volume":1.5,"vwap":66020.0,"interval":5,
+             "interval_begin":"2024-06-10T14:30:00.000000000Z",
+             "timestamp":"2024-06-10T14:35:00.000000Z"},
+            {"symbol":"BTC/USD","open":66050.0,"high":66200.0,"low":66000.0,"close":66180.0,
+             "trades":8,"volume":0.9,"vwap":66120.0,"interval":5,
+             "interval_begin":"2024-06-10T14:35:00.000000000Z",
+             "timestamp":"2024-06-10T14:40:00.000000Z"}
+        ]}"#;
+
+        let candle = |begin, high, low, close| PriceEvent::Candle {
+            symbol: "BTC/USD".to_string(),
+            candle: Candle { begin, high, low, close },
+        };
+        assert_eq!(
+            parse_ticker(frame),
+            vec![
+                candle(1718029800, 66100.5, 65950.0, 66050.0),
+                candle(1718030100, 66200.0, 66000.0, 66180.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_from_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let Some(Ok(Message::Text(subscribe))) = ws.next().await else {
+                panic!("expected a subscribe request");
+            };
+            let ticker = r#"{"channel":"ticker","type":"snapshot","data":[{"symbol":"BTC/USD",
+                "bid":66000.0,"ask":66010.0,"last":66005.0,"volume":1200.5,"vwap":65800.0,
+                "low":64900.0,"high":66500.0,"change":1005.0,"change_pct":1.55}]}"#;
+            let rejected = r#"{"method":"subscribe","success":false,
+                "error":"Currency pair not supported DOGE/USDT","symbol":"DOGE/USDT"}"#;
+            ws.send(Message::Text(ticker.to_string())).await.unwrap();
+            ws.send(Message::Text(rejected.to_string())).await.unwrap();
+            ws.close(None).await.unwrap();
+            subscribe
+        });
+
+        let mut config = Config::default();
+        config.coins.truncate(1);
+        let mut doge = config.coins[0].clone();
+        doge.symbol = "DOGE/USDT".to_string();
+        doge.name = "DOGE".to_string();
+        config.coins.push(doge);
+        let state = Arc::new(Mutex::new(TickerState::new(&config, false)));
+
+        let symbols = vec!["BTC/USD".to_string(), "DOGE/USDT".to_string()];
+        let map = symbols.iter().map(|s| (s.clone(), s.clone())).collect();
+        let sink = PriceSink::new(state.clone(), "kraken", map);
+        let endpoint = Endpoint { url, watchdog: None, proxy: None, tls: None };
+        KrakenSource::new(endpoint).stream(&symbols, &sink).await.unwrap();
+
+        let subscribe = server.await.unwrap();
+        assert!(subscribe.contains(r#""channel":"ticker""#), "{}", subscribe);
+        let state = state.lock().unwrap();
+        let texts: Vec<&str> = state.segments.iter().map(|s| s.text.as_str()).collect();
+        assert!(texts.iter().any(|t| t.contains("$66005")), "{:?}", texts);
+        assert!(
+            texts.iter().any(|t| t.contains("⚠ kraken: Currency pair not supported")),
+            "{:?}",
+            texts
+        );
+    }
 }